@@ -0,0 +1,479 @@
+use cgmath::{InnerSpace, Matrix4, One, Quaternion, SquareMatrix, Vector3, VectorSpace};
+use std::rc::Rc;
+
+use super::vertex_format;
+
+/// The size of the `joints` uniform array in the vertex shaders.
+pub const MAX_JOINTS: usize = 64;
+
+vertex_format! {
+    /// A vertex moved by up to four joints. Locations match `VertexData` where the attributes overlap.
+    pub struct SkinnedVertex {
+        0 => pub position: [f32; 3],
+        1 => pub color: [f32; 3],
+        2 => pub texture_coordinates: [f32; 2],
+        3 => pub normal: [f32; 3],
+        5 => pub joints: [u16; 4],
+        6 => pub weights: [f32; 4],
+    }
+}
+
+/// Translation, rotation and scale of a joint relative to its parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JointTransform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl JointTransform {
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+pub struct Joint {
+    pub name: Option<String>,
+    /// Joints without a parent are placed by `origin`.
+    pub parent: Option<usize>,
+    /// Transform of whatever is above a root joint, identity for the others.
+    pub origin: Matrix4<f32>,
+    /// Moves vertices from the model space into the joint's space in the bind pose.
+    pub inverse_bind: Matrix4<f32>,
+    /// Used where an animation does not move the joint.
+    pub rest: JointTransform,
+}
+
+pub struct Skeleton {
+    joints: Vec<Joint>,
+    /// Joints ordered so that parents always come before their children.
+    order: Vec<usize>,
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>) -> Self {
+        let mut order = Vec::with_capacity(joints.len());
+        let mut placed = vec![false; joints.len()];
+        while order.len() < joints.len() {
+            let before = order.len();
+            for (index, joint) in joints.iter().enumerate() {
+                let ready = joint.parent.is_none_or(|parent| placed[parent]);
+                if !placed[index] && ready {
+                    placed[index] = true;
+                    order.push(index);
+                }
+            }
+            assert!(order.len() > before, "The joints form a cycle");
+        }
+        Self { joints, order }
+    }
+
+    pub fn joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    pub fn joint_named(&self, name: &str) -> Option<usize> {
+        self.joints
+            .iter()
+            .position(|joint| joint.name.as_deref() == Some(name))
+    }
+
+    pub fn rest_pose(&self) -> Vec<JointTransform> {
+        self.joints.iter().map(|joint| joint.rest).collect()
+    }
+
+    /// The matrices for the `joints` uniform: each moves a vertex from the bind pose to `pose`.
+    pub fn joint_matrices(&self, pose: &[JointTransform]) -> Vec<Matrix4<f32>> {
+        let mut global = vec![Matrix4::identity(); self.joints.len()];
+        for &index in self.order.iter() {
+            let joint = &self.joints[index];
+            let parent = match joint.parent {
+                Some(parent) => global[parent],
+                None => joint.origin,
+            };
+            global[index] = parent * pose[index].matrix();
+        }
+        global
+            .iter()
+            .zip(self.joints.iter())
+            .map(|(global, joint)| global * joint.inverse_bind)
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    /// Every keyframe is stored as an in tangent, the value and an out tangent, like in glTF.
+    CubicSpline,
+}
+
+pub enum Keyframes {
+    Translation(Vec<Vector3<f32>>),
+    Rotation(Vec<Quaternion<f32>>),
+    Scale(Vec<Vector3<f32>>),
+}
+
+/// Keyframes of one property of one joint.
+pub struct Channel {
+    pub joint: usize,
+    pub times: Vec<f32>,
+    pub keyframes: Keyframes,
+    pub interpolation: Interpolation,
+}
+
+impl Channel {
+    /// Why the keyframes cannot be sampled: there has to be at least one, with one value
+    /// for every time or three for cubic splines.
+    pub(super) fn check(&self) -> Result<(), String> {
+        let values = match &self.keyframes {
+            Keyframes::Translation(values) | Keyframes::Scale(values) => values.len(),
+            Keyframes::Rotation(values) => values.len(),
+        };
+        let per_key = match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+        if self.times.is_empty() {
+            return Err("There has to be at least one keyframe".to_string());
+        }
+        if values != self.times.len() * per_key {
+            return Err(format!(
+                "{} keyframe values for {} times with {:?} interpolation",
+                values,
+                self.times.len(),
+                self.interpolation
+            ));
+        }
+        Ok(())
+    }
+
+    fn apply(&self, time: f32, transform: &mut JointTransform) {
+        match &self.keyframes {
+            Keyframes::Translation(values) => {
+                transform.translation =
+                    sample_keyframes(&self.times, self.interpolation, values, time, |a, b, t| {
+                        a.lerp(b, t)
+                    });
+            }
+            Keyframes::Scale(values) => {
+                transform.scale =
+                    sample_keyframes(&self.times, self.interpolation, values, time, |a, b, t| {
+                        a.lerp(b, t)
+                    });
+            }
+            Keyframes::Rotation(values) => {
+                let rotation =
+                    sample_keyframes(&self.times, self.interpolation, values, time, |a, b, t| {
+                        // The shorter way around.
+                        let b = if a.dot(b) < 0.0 { -b } else { b };
+                        a.slerp(b, t)
+                    });
+                transform.rotation = rotation.normalize();
+            }
+        }
+    }
+}
+
+/// The value of keyframes at `time`, holding the first and the last one outside of `times`.
+pub(super) fn sample_keyframes<T, F>(
+    times: &[f32],
+    interpolation: Interpolation,
+    values: &[T],
+    time: f32,
+    interpolate: F,
+) -> T
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Mul<f32, Output = T>,
+    F: Fn(T, T, f32) -> T,
+{
+    let cubic = interpolation == Interpolation::CubicSpline;
+    let value = |key: usize| {
+        if cubic {
+            values[key * 3 + 1]
+        } else {
+            values[key]
+        }
+    };
+
+    let last = times.len() - 1;
+    let next = times.partition_point(|t| *t <= time);
+    if next == 0 {
+        return value(0);
+    }
+    if next > last {
+        return value(last);
+    }
+    let key = next - 1;
+    let span = times[next] - times[key];
+    let t = (time - times[key]) / span;
+    match interpolation {
+        Interpolation::Step => value(key),
+        Interpolation::Linear => interpolate(value(key), value(next), t),
+        Interpolation::CubicSpline => {
+            let (t2, t3) = (t * t, t * t * t);
+            let out_tangent = values[key * 3 + 2] * span;
+            let in_tangent = values[next * 3] * span;
+            value(key) * (2.0 * t3 - 3.0 * t2 + 1.0)
+                + out_tangent * (t3 - 2.0 * t2 + t)
+                + value(next) * (-2.0 * t3 + 3.0 * t2)
+                + in_tangent * (t3 - t2)
+        }
+    }
+}
+
+pub struct AnimationClip {
+    pub name: Option<String>,
+    /// The time of the last keyframe, in seconds.
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+impl AnimationClip {
+    pub fn new(name: Option<String>, channels: Vec<Channel>) -> Self {
+        let duration = channels
+            .iter()
+            .filter_map(|channel| channel.times.last().cloned())
+            .fold(0.0, f32::max);
+        Self {
+            name,
+            duration,
+            channels,
+        }
+    }
+
+    /// The pose at `time` seconds. Joints without channels stay in their rest pose.
+    pub fn sample(&self, skeleton: &Skeleton, time: f32) -> Vec<JointTransform> {
+        let mut pose = skeleton.rest_pose();
+        for channel in self.channels.iter() {
+            if !channel.times.is_empty() {
+                channel.apply(time, &mut pose[channel.joint]);
+            }
+        }
+        pose
+    }
+}
+
+/// A skeleton together with the animations made for it.
+pub struct Skin {
+    pub skeleton: Skeleton,
+    pub animations: Vec<AnimationClip>,
+}
+
+/// Plays the animations of a skin and keeps the joint matrices of the current frame.
+pub struct Animator {
+    skin: Rc<Skin>,
+    clip: Option<usize>,
+    time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    matrices: Vec<Matrix4<f32>>,
+}
+
+impl Animator {
+    /// Starts in the rest pose.
+    pub fn new(skin: Rc<Skin>) -> Self {
+        let matrices = skin.skeleton.joint_matrices(&skin.skeleton.rest_pose());
+        Self {
+            skin,
+            clip: None,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+            matrices,
+        }
+    }
+
+    pub fn skin(&self) -> &Rc<Skin> {
+        &self.skin
+    }
+
+    /// Plays the clip from the start, `false` when the skin has no clip called `name`.
+    pub fn play(&mut self, name: &str) -> bool {
+        let clip = self
+            .skin
+            .animations
+            .iter()
+            .position(|clip| clip.name.as_deref() == Some(name));
+        if let Some(clip) = clip {
+            self.play_index(clip);
+        }
+        clip.is_some()
+    }
+
+    pub fn play_index(&mut self, clip: usize) {
+        self.clip = Some(clip);
+        self.time = 0.0;
+        self.update_matrices();
+    }
+
+    pub fn stop(&mut self) {
+        self.clip = None;
+        self.update_matrices();
+    }
+
+    pub fn advance(&mut self, secs: f32) {
+        if self.clip.is_none() {
+            return;
+        }
+        self.time += secs * self.speed;
+        self.update_matrices();
+    }
+
+    pub fn joint_matrices(&self) -> &[Matrix4<f32>] {
+        &self.matrices
+    }
+
+    fn update_matrices(&mut self) {
+        let skeleton = &self.skin.skeleton;
+        let pose = match self.clip {
+            Some(clip) => {
+                let clip = &self.skin.animations[clip];
+                let time = if self.looping && clip.duration > 0.0 {
+                    self.time.rem_euclid(clip.duration)
+                } else {
+                    self.time
+                };
+                clip.sample(skeleton, time)
+            }
+            None => skeleton.rest_pose(),
+        };
+        self.matrices = skeleton.joint_matrices(&pose);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Rotation3};
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn arm() -> Skeleton {
+        let joint = |parent, y: f32| Joint {
+            name: None,
+            parent,
+            origin: Matrix4::identity(),
+            inverse_bind: Matrix4::from_translation(Vector3::new(0.0, -y, 0.0)),
+            rest: JointTransform {
+                translation: Vector3::new(0.0, if parent.is_some() { 1.0 } else { 0.0 }, 0.0),
+                ..JointTransform::default()
+            },
+        };
+        // Children first on purpose.
+        Skeleton::new(vec![joint(Some(1), 1.0), joint(None, 0.0)])
+    }
+
+    #[test]
+    fn rest_pose_is_identity() {
+        let skeleton = arm();
+        for matrix in skeleton.joint_matrices(&skeleton.rest_pose()) {
+            assert_eq!(matrix, Matrix4::identity());
+        }
+    }
+
+    #[test]
+    fn interpolation() {
+        let channel = |interpolation, values| Channel {
+            joint: 0,
+            times: vec![1.0, 3.0],
+            keyframes: Keyframes::Translation(values),
+            interpolation,
+        };
+        let x = |x| Vector3::new(x, 0.0, 0.0);
+        let sample = |channel: &Channel, time| {
+            let mut transform = JointTransform::default();
+            channel.apply(time, &mut transform);
+            transform.translation
+        };
+
+        let linear = channel(Interpolation::Linear, vec![x(0.0), x(4.0)]);
+        assert_close(sample(&linear, 0.0), x(0.0));
+        assert_close(sample(&linear, 2.5), x(3.0));
+        assert_close(sample(&linear, 10.0), x(4.0));
+
+        let step = channel(Interpolation::Step, vec![x(0.0), x(4.0)]);
+        assert_close(sample(&step, 2.9), x(0.0));
+        assert_close(sample(&step, 3.0), x(4.0));
+
+        // Flat tangents ease in and out, passing the middle at half the way.
+        let zero = x(0.0);
+        let cubic = channel(
+            Interpolation::CubicSpline,
+            vec![zero, x(0.0), zero, zero, x(4.0), zero],
+        );
+        assert_close(sample(&cubic, 2.0), x(2.0));
+        assert!(sample(&cubic, 1.5).x < 1.0);
+
+        assert_eq!(linear.check(), Ok(()));
+        assert_eq!(cubic.check(), Ok(()));
+        assert_eq!(
+            channel(Interpolation::CubicSpline, vec![zero, x(0.0), zero]).check(),
+            Err("3 keyframe values for 2 times with CubicSpline interpolation".to_string())
+        );
+    }
+
+    #[test]
+    fn rotations_take_the_short_way() {
+        let channel = Channel {
+            joint: 0,
+            times: vec![0.0, 1.0],
+            keyframes: Keyframes::Rotation(vec![
+                Quaternion::from_angle_z(Deg(10.0)),
+                -Quaternion::from_angle_z(Deg(30.0)),
+            ]),
+            interpolation: Interpolation::Linear,
+        };
+        let mut transform = JointTransform::default();
+        channel.apply(0.5, &mut transform);
+        let expected = Quaternion::from_angle_z(Deg(20.0));
+        assert!(transform.rotation.dot(expected).abs() > 0.9999);
+    }
+
+    #[test]
+    fn animated_joint_matrices() {
+        let skeleton = arm();
+        let clip = AnimationClip::new(
+            Some("wave".to_string()),
+            vec![Channel {
+                joint: 1,
+                times: vec![0.0, 1.0, 2.0],
+                keyframes: Keyframes::Rotation(vec![
+                    Quaternion::one(),
+                    Quaternion::from_angle_z(Deg(90.0)),
+                    Quaternion::one(),
+                ]),
+                interpolation: Interpolation::Linear,
+            }],
+        );
+        assert_eq!(clip.duration, 2.0);
+
+        // At the middle key the root turned by 90 degrees, taking the child at y = 1 to x = -1.
+        let matrices = skeleton.joint_matrices(&clip.sample(&skeleton, 1.0));
+        let tip = matrices[0] * cgmath::Vector4::new(0.0, 2.0, 0.0, 1.0);
+        assert_close(tip.truncate(), Vector3::new(-2.0, 0.0, 0.0));
+
+        let mut animator = Animator::new(Rc::new(Skin {
+            skeleton,
+            animations: vec![clip],
+        }));
+        assert!(animator.play("wave"));
+        animator.advance(3.0);
+        assert_eq!(animator.joint_matrices(), matrices.as_slice());
+    }
+}
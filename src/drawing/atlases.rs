@@ -0,0 +1,281 @@
+use image::RgbaImage;
+use std::{collections::HashMap, path::Path, rc::Rc};
+
+use super::{models::MeshData, Error, Texture, TextureOptions, Wrap};
+
+/// A part of a texture in texture coordinates, `min` being the top left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UvRect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl UvRect {
+    /// Moves coordinates meant for a whole texture into the rectangle.
+    pub fn map(&self, [u, v]: [f32; 2]) -> [f32; 2] {
+        [
+            self.min[0] + u * (self.max[0] - self.min[0]),
+            self.min[1] + v * (self.max[1] - self.min[1]),
+        ]
+    }
+}
+
+impl MeshData {
+    /// Makes a mesh textured with a whole image use its part of an atlas instead.
+    /// Coordinates outside of 0..1 do not repeat in an atlas, so they are not supported.
+    pub fn map_texture_coordinates(&mut self, rect: &UvRect) {
+        for vertex in self.vertices.iter_mut() {
+            let (u, v) = vertex.texture_coordinates();
+            vertex.set_texture_coordinates(rect.map([u, v]));
+        }
+    }
+}
+
+/// Collects named images to pack into one texture.
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+    padding: u32,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self {
+            images: vec![],
+            padding: 4,
+        }
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pixels around every image repeating its edges. Every mip level halves them,
+    /// so `2^n` pixels keep `n` levels from mixing neighbours together. 4 by default.
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// An image added under a name that is already taken replaces the old one.
+    /// Images without pixels have no edges to pad with, so they are rejected.
+    pub fn add(&mut self, name: &str, image: RgbaImage) -> Result<&mut Self, Error> {
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::EmptyImage {
+                name: name.to_string(),
+            });
+        }
+        self.images.retain(|(other, _)| other != name);
+        self.images.push((name.to_string(), image));
+        Ok(self)
+    }
+
+    pub fn add_file(&mut self, name: &str, file_name: &Path) -> Result<&mut Self, Error> {
+        let image = image::open(file_name)
+            .map_err(|source| Error::Image {
+                path: file_name.to_path_buf(),
+                source,
+            })?
+            .into_rgba8();
+        self.add(name, image)
+    }
+
+    /// Places the images on shelves, tallest first, in a power of two sized image.
+    pub fn pack(&self) -> (RgbaImage, HashMap<String, UvRect>) {
+        let padding = self.padding;
+        let cell = |image: &RgbaImage| (image.width() + 2 * padding, image.height() + 2 * padding);
+
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&self.images[a], &self.images[b]);
+            b.1.height().cmp(&a.1.height()).then(a.0.cmp(&b.0))
+        });
+
+        let area: u32 = self
+            .images
+            .iter()
+            .map(|(_, image)| cell(image).0 * cell(image).1)
+            .sum();
+        let widest = self.images.iter().map(|(_, image)| cell(image).0).max();
+        let mut width = ((area as f32).sqrt().ceil() as u32)
+            .max(widest.unwrap_or(1))
+            .next_power_of_two();
+        let (height, positions) = loop {
+            let (height, positions) = shelves(&self.images, &order, width, cell);
+            if height <= width {
+                break (height.next_power_of_two(), positions);
+            }
+            width *= 2;
+        };
+
+        let mut atlas = RgbaImage::new(width, height);
+        let mut rects = HashMap::new();
+        for ((name, image), (x, y)) in self.images.iter().zip(positions) {
+            blit_padded(&mut atlas, image, x, y, padding);
+            let (left, top) = ((x + padding) as f32, (y + padding) as f32);
+            let rect = UvRect {
+                min: [left / width as f32, top / height as f32],
+                max: [
+                    (left + image.width() as f32) / width as f32,
+                    (top + image.height() as f32) / height as f32,
+                ],
+            };
+            rects.insert(name.clone(), rect);
+        }
+        (atlas, rects)
+    }
+
+    /// Wrapping is switched to clamping, repeating would show the neighbouring images.
+    pub fn build(&self, gl: gl::Gl, options: TextureOptions) -> Atlas {
+        let (image, rects) = self.pack();
+        let options = options.with_wrap(Wrap::ClampToEdge);
+        Atlas {
+            texture: Rc::new(Texture::from_rgba8(gl, image, options)),
+            rects,
+        }
+    }
+}
+
+/// Top left corners of the padded images, in the order of `images`, and the height used.
+fn shelves(
+    images: &[(String, RgbaImage)],
+    order: &[usize],
+    width: u32,
+    cell: impl Fn(&RgbaImage) -> (u32, u32),
+) -> (u32, Vec<(u32, u32)>) {
+    let mut positions = vec![(0, 0); images.len()];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for &index in order.iter() {
+        let (w, h) = cell(&images[index].1);
+        if x + w > width {
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+        positions[index] = (x, y);
+        x += w;
+        shelf_height = shelf_height.max(h);
+    }
+    (y + shelf_height, positions)
+}
+
+/// Copies `image` to `x + padding`, `y + padding` and fills the padding with its edges.
+fn blit_padded(atlas: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32, padding: u32) {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let padding = padding as i64;
+    for dy in -padding..height + padding {
+        for dx in -padding..width + padding {
+            let pixel = image.get_pixel(
+                dx.clamp(0, width - 1) as u32,
+                dy.clamp(0, height - 1) as u32,
+            );
+            atlas.put_pixel(
+                (x as i64 + padding + dx) as u32,
+                (y as i64 + padding + dy) as u32,
+                *pixel,
+            );
+        }
+    }
+}
+
+/// One texture holding many images, found by name.
+pub struct Atlas {
+    texture: Rc<Texture>,
+    rects: HashMap<String, UvRect>,
+}
+
+impl Atlas {
+    pub fn texture(&self) -> &Rc<Texture> {
+        &self.texture
+    }
+
+    pub fn rect(&self, name: &str) -> Option<UvRect> {
+        self.rects.get(name).cloned()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.rects.keys().map(|name| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn packing() {
+        let mut builder = AtlasBuilder::new().with_padding(2);
+        let red = RgbaImage::from_pixel(16, 16, Rgba([255, 0, 0, 255]));
+        let green = RgbaImage::from_pixel(8, 4, Rgba([0, 255, 0, 255]));
+        let blue = RgbaImage::from_pixel(4, 8, Rgba([0, 0, 255, 255]));
+        builder
+            .add("red", red)
+            .unwrap()
+            .add("green", green)
+            .unwrap()
+            .add("blue", blue)
+            .unwrap();
+        let (atlas, rects) = builder.pack();
+        assert!(atlas.width().is_power_of_two() && atlas.height().is_power_of_two());
+
+        let to_pixels = |rect: &UvRect| {
+            let (w, h) = (atlas.width() as f32, atlas.height() as f32);
+            let x = (rect.min[0] * w) as u32..(rect.max[0] * w) as u32;
+            let y = (rect.min[1] * h) as u32..(rect.max[1] * h) as u32;
+            (x, y)
+        };
+        let mut covered = vec![false; (atlas.width() * atlas.height()) as usize];
+        for (name, color) in [
+            ("red", [255, 0, 0, 255]),
+            ("green", [0, 255, 0, 255]),
+            ("blue", [0, 0, 255, 255]),
+        ] {
+            let (xs, ys) = to_pixels(&rects[name]);
+            // The padding repeats the edges too.
+            for y in ys.start - 2..ys.end + 2 {
+                for x in xs.start - 2..xs.end + 2 {
+                    assert_eq!(atlas.get_pixel(x, y).0, color, "{} at {}, {}", name, x, y);
+                    let covered = &mut covered[(y * atlas.width() + x) as usize];
+                    assert!(!*covered, "{} overlaps another image at {}, {}", name, x, y);
+                    *covered = true;
+                }
+            }
+        }
+        let (xs, ys) = to_pixels(&rects["green"]);
+        assert_eq!((xs.len(), ys.len()), (8, 4));
+    }
+
+    #[test]
+    fn empty_images() {
+        let mut builder = AtlasBuilder::new();
+        for (width, height) in [(0, 4), (4, 0), (0, 0)] {
+            let error = builder.add("empty", RgbaImage::new(width, height)).err();
+            assert!(matches!(error, Some(Error::EmptyImage { name }) if name == "empty"));
+        }
+        builder.add("pixel", RgbaImage::new(1, 1)).unwrap();
+
+        let (atlas, rects) = builder.pack();
+        assert_eq!(rects.len(), 1);
+        assert!(rects.contains_key("pixel"));
+        assert!(atlas.width() >= 9 && atlas.height() >= 9);
+    }
+
+    #[test]
+    fn mapping() {
+        let rect = UvRect {
+            min: [0.5, 0.25],
+            max: [1.0, 0.5],
+        };
+        assert_eq!(rect.map([0.0, 0.0]), [0.5, 0.25]);
+        assert_eq!(rect.map([0.5, 1.0]), [0.75, 0.5]);
+
+        let mut cube = MeshData::cube();
+        cube.map_texture_coordinates(&rect);
+        for vertex in cube.vertices.iter() {
+            let (u, v) = vertex.texture_coordinates();
+            assert!((0.5..=1.0).contains(&u) && (0.25..=0.5).contains(&v));
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use cgmath::{InnerSpace, Matrix4, Point3, Transform, Vector3};
+
+/// Axis aligned bounding box. An empty box has `min` above `max` and contains nothing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn empty() -> Self {
+        Self {
+            min: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Vector3<f32>>>(points: I) -> Self {
+        points.into_iter().fold(Self::empty(), |aabb, point| {
+            aabb.union(&Self {
+                min: point,
+                max: point,
+            })
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vector3::new(a.x, a.y, a.z),
+            Vector3::new(a.x, a.y, b.z),
+            Vector3::new(a.x, b.y, a.z),
+            Vector3::new(a.x, b.y, b.z),
+            Vector3::new(b.x, a.y, a.z),
+            Vector3::new(b.x, a.y, b.z),
+            Vector3::new(b.x, b.y, a.z),
+            Vector3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: Vector3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn contains(&self, point: Vector3<f32>) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+            && self.min.z <= other.max.z
+            && other.min.z <= self.max.z
+    }
+
+    /// The box around the transformed corners, e.g. to move a model's box into the world.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        Self::from_points(
+            self.corners()
+                .iter()
+                .map(|c| matrix.transform_point(Point3::new(c.x, c.y, c.z)))
+                .map(|p| Vector3::new(p.x, p.y, p.z)),
+        )
+    }
+
+    /// Distance along `direction` to the first hit, in units of `direction`'s length.
+    /// A ray starting inside the box hits it at 0.
+    pub fn ray_intersection(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            let inverse = 1.0 / direction[axis];
+            let mut t0 = (self.min[axis] - origin[axis]) * inverse;
+            let mut t1 = (self.max[axis] - origin[axis]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN comes from a ray lying in a slab's plane, `max`/`min` skip it.
+            near = near.max(t0);
+            far = far.min(t1);
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Centered on the bounding box of the points, which is close enough for culling.
+    pub fn from_points(points: &[Vector3<f32>]) -> Self {
+        let center = Aabb::from_points(points.iter().cloned()).center();
+        if points.is_empty() {
+            return Self {
+                center: Vector3::new(0.0, 0.0, 0.0),
+                radius: 0.0,
+            };
+        }
+        let radius = points
+            .iter()
+            .map(|p| (p - center).magnitude())
+            .fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    /// A sphere around both `self` and `other`, centered on `center`.
+    pub fn enclose(&self, center: Vector3<f32>, other: &BoundingSphere) -> Self {
+        let radius = ((self.center - center).magnitude() + self.radius)
+            .max((other.center - center).magnitude() + other.radius);
+        Self { center, radius }
+    }
+
+    pub fn contains(&self, point: Vector3<f32>) -> bool {
+        (point - self.center).magnitude2() <= self.radius * self.radius
+    }
+
+    pub fn intersects(&self, other: &BoundingSphere) -> bool {
+        let distance = self.radius + other.radius;
+        (self.center - other.center).magnitude2() <= distance * distance
+    }
+
+    /// Scales the radius by the longest axis of `matrix`, so the result still encloses the shape.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let center =
+            matrix.transform_point(Point3::new(self.center.x, self.center.y, self.center.z));
+        let scale = [matrix.x, matrix.y, matrix.z]
+            .iter()
+            .map(|axis| axis.truncate().magnitude())
+            .fold(0.0, f32::max);
+        Self {
+            center: Vector3::new(center.x, center.y, center.z),
+            radius: self.radius * scale,
+        }
+    }
+
+    /// Distance along the normalized `direction` to the first hit, 0 when starting inside.
+    pub fn ray_intersection(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let to_center = self.center - origin;
+        let along = to_center.dot(direction);
+        let squared = self.radius * self.radius - (to_center.magnitude2() - along * along);
+        if squared < 0.0 {
+            return None;
+        }
+        let half_chord = squared.sqrt();
+        if along + half_chord < 0.0 {
+            None
+        } else {
+            Some((along - half_chord).max(0.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aabb() {
+        let aabb = Aabb::from_points(vec![
+            Vector3::new(-1.0, 0.0, 2.0),
+            Vector3::new(1.0, 3.0, -2.0),
+        ]);
+        assert_eq!(aabb.min, Vector3::new(-1.0, 0.0, -2.0));
+        assert_eq!(aabb.max, Vector3::new(1.0, 3.0, 2.0));
+        assert!(aabb.contains(Vector3::new(0.0, 1.0, 0.0)));
+        assert!(!aabb.contains(Vector3::new(0.0, 4.0, 0.0)));
+
+        assert!(Aabb::empty().is_empty());
+        assert_eq!(aabb.union(&Aabb::empty()), aabb);
+
+        let moved = aabb.transform(&Matrix4::from_translation(Vector3::new(10.0, 0.0, 0.0)));
+        assert_eq!(moved.min, Vector3::new(9.0, 0.0, -2.0));
+        assert!(!moved.intersects(&aabb));
+    }
+
+    #[test]
+    fn rays() {
+        let aabb = Aabb {
+            min: Vector3::new(-1.0, -1.0, -1.0),
+            max: Vector3::new(1.0, 1.0, 1.0),
+        };
+        let origin = Vector3::new(0.0, 0.0, 5.0);
+        let forward = Vector3::new(0.0, 0.0, -1.0);
+        assert_eq!(aabb.ray_intersection(origin, forward), Some(4.0));
+        assert_eq!(aabb.ray_intersection(origin, -forward), None);
+        assert_eq!(
+            aabb.ray_intersection(Vector3::new(2.0, 0.0, 5.0), forward),
+            None
+        );
+
+        let sphere = BoundingSphere {
+            center: Vector3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+        };
+        assert_eq!(sphere.ray_intersection(origin, forward), Some(4.0));
+        assert_eq!(sphere.ray_intersection(origin, -forward), None);
+        assert_eq!(
+            sphere.ray_intersection(Vector3::new(0.0, 0.0, 0.0), forward),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn spheres() {
+        let sphere = BoundingSphere::from_points(&[
+            Vector3::new(-1.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.5, 0.0),
+        ]);
+        assert_eq!(sphere.center, Vector3::new(0.0, 0.25, 0.0));
+        assert!(sphere.contains(Vector3::new(1.0, 0.0, 0.0)));
+
+        let scaled = sphere.transform(&Matrix4::from_scale(2.0));
+        assert_eq!(scaled.center, Vector3::new(0.0, 0.5, 0.0));
+        assert_eq!(scaled.radius, sphere.radius * 2.0);
+        assert!(scaled.intersects(&sphere));
+    }
+}
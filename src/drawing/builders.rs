@@ -0,0 +1,98 @@
+use cgmath::Vector3;
+
+use super::{
+    models::{MeshData, VertexData},
+    Model,
+};
+
+/// Collects vertices and faces into `MeshData`. Quads and polygons are split into triangles
+/// as they are added, so the result can be drawn with `gl::TRIANGLES` on core profiles.
+#[derive(Default)]
+pub struct MeshBuilder {
+    data: MeshData,
+}
+
+impl MeshBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the index to refer to the vertex in faces.
+    pub fn add_vertex(&mut self, vertex: VertexData) -> u32 {
+        self.data.vertices.push(vertex);
+        (self.data.vertices.len() - 1) as u32
+    }
+
+    /// Adds a white vertex.
+    pub fn add_point(
+        &mut self,
+        position: Vector3<f32>,
+        texture_coordinates: [f32; 2],
+        normal: Vector3<f32>,
+    ) -> u32 {
+        self.add_vertex(VertexData::new(
+            position.into(),
+            texture_coordinates,
+            [1.0, 1.0, 1.0],
+            normal.into(),
+        ))
+    }
+
+    /// Corners go counterclockwise as seen from the front.
+    pub fn add_triangle(&mut self, a: u32, b: u32, c: u32) -> &mut Self {
+        self.data.indices.extend_from_slice(&[a, b, c]);
+        self
+    }
+
+    /// Split along the `a`-`c` diagonal.
+    pub fn add_quad(&mut self, a: u32, b: u32, c: u32, d: u32) -> &mut Self {
+        self.add_triangle(a, b, c).add_triangle(a, c, d)
+    }
+
+    /// Split as a fan around the first corner, so the polygon has to be convex.
+    pub fn add_polygon(&mut self, corners: &[u32]) -> &mut Self {
+        for i in 1..corners.len().saturating_sub(1) {
+            self.add_triangle(corners[0], corners[i], corners[i + 1]);
+        }
+        self
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.data.vertices.len()
+    }
+
+    pub fn build(self) -> MeshData {
+        self.data
+    }
+
+    pub fn build_model(self, gl: gl::Gl) -> Model {
+        Model::from_mesh_data(gl, &self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triangulation() {
+        let mut builder = MeshBuilder::new();
+        let corners: Vec<u32> = (0..5)
+            .map(|i| {
+                builder.add_point(
+                    Vector3::new(i as f32, 0.0, 0.0),
+                    [0.0, 0.0],
+                    Vector3::unit_y(),
+                )
+            })
+            .collect();
+        builder
+            .add_quad(corners[0], corners[1], corners[2], corners[3])
+            .add_polygon(&corners);
+        assert_eq!(builder.vertex_count(), 5);
+        assert_eq!(
+            builder.build().indices,
+            vec![0, 1, 2, 0, 2, 3, 0, 1, 2, 0, 2, 3, 0, 3, 4]
+        );
+    }
+}
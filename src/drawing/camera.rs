@@ -1,5 +1,7 @@
 use cgmath::{Matrix3, Matrix4, One, Point3, Rad, Vector3};
 
+const FIELD_OF_VIEW: Rad<f32> = Rad(1.0);
+
 pub struct Camera {
     pub origin: Point3<f32>,
     h_angle: Rad<f32>,
@@ -22,14 +24,18 @@ impl Camera {
     }
 
     fn recalculate_matrix(&mut self) {
+        self.matrix = self.matrix_from(self.origin);
+    }
+
+    fn matrix_from(&self, origin: Point3<f32>) -> Matrix4<f32> {
         let rotate =
             Matrix3::<f32>::from_angle_y(self.h_angle) * Matrix3::<f32>::from_angle_z(self.v_angle);
-        self.matrix = cgmath::perspective(Rad(1.0), self.aspect, 0.1, 10000.0)
+        cgmath::perspective(FIELD_OF_VIEW, self.aspect, 0.1, 10000.0)
             * Matrix4::look_at(
-                self.origin,
-                self.origin + rotate * Vector3::unit_x(),
+                origin,
+                origin + rotate * Vector3::unit_x(),
                 rotate * Vector3::unit_y(),
-            );
+            )
     }
 
     pub fn rotate_horisontal(&mut self, angle: Rad<f32>) {
@@ -57,7 +63,6 @@ impl Camera {
         self.recalculate_matrix();
     }
 
-    #[allow(dead_code)]
     pub fn get_forvard(&self) -> Vector3<f32>{
         Matrix3::<f32>::from_angle_y(self.h_angle) * Matrix3::<f32>::from_angle_z(self.v_angle) * Vector3::unit_x()
     }
@@ -70,4 +75,14 @@ impl Camera {
     pub fn matrix(&self) -> Matrix4<f32> {
         self.matrix
     }
+
+    /// Like `matrix` with the camera kept at the origin, for what is infinitely far away.
+    pub fn rotation_matrix(&self) -> Matrix4<f32> {
+        self.matrix_from(Point3::new(0.0, 0.0, 0.0))
+    }
+
+    /// Vertical, in radians.
+    pub fn field_of_view(&self) -> Rad<f32> {
+        FIELD_OF_VIEW
+    }
 }
@@ -0,0 +1,345 @@
+use gl::{types::GLenum, Gl};
+use std::{convert::TryInto, path::Path};
+
+use super::{
+    dds::parse_dds, gl_version, has_extension, ktx2::parse_ktx2, Error, Texture, TextureOptions,
+};
+
+// From EXT_texture_compression_s3tc, which never became core, so the bindings lack them.
+const COMPRESSED_RGBA_S3TC_DXT1: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3: GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5: GLenum = 0x83F3;
+
+/// Block compressed formats, every block holding 4x4 pixels. sRGB variants are loaded
+/// as their linear counterparts, like every other texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockFormat {
+    /// DXT1, colors with an optional 1 bit alpha.
+    Bc1,
+    /// DXT3, colors with 4 bit alpha.
+    Bc2,
+    /// DXT5, colors with interpolated alpha.
+    Bc3,
+    /// Red only.
+    Bc4,
+    /// Red and green, e.g. for normal maps.
+    Bc5,
+    /// HDR colors, only loaded where the GPU supports them.
+    Bc6hUnsigned,
+    Bc6hSigned,
+    /// Only loaded where the GPU supports it.
+    Bc7,
+}
+
+impl BlockFormat {
+    pub fn block_bytes(self) -> usize {
+        match self {
+            BlockFormat::Bc1 | BlockFormat::Bc4 => 8,
+            _ => 16,
+        }
+    }
+
+    /// Whether the blocks can be turned into RGBA8 pixels where the GPU cannot sample them.
+    pub fn can_decompress(self) -> bool {
+        !matches!(
+            self,
+            BlockFormat::Bc6hUnsigned | BlockFormat::Bc6hSigned | BlockFormat::Bc7
+        )
+    }
+
+    pub(super) fn internal_format(self) -> GLenum {
+        match self {
+            BlockFormat::Bc1 => COMPRESSED_RGBA_S3TC_DXT1,
+            BlockFormat::Bc2 => COMPRESSED_RGBA_S3TC_DXT3,
+            BlockFormat::Bc3 => COMPRESSED_RGBA_S3TC_DXT5,
+            BlockFormat::Bc4 => gl::COMPRESSED_RED_RGTC1,
+            BlockFormat::Bc5 => gl::COMPRESSED_RG_RGTC2,
+            BlockFormat::Bc6hUnsigned => gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            BlockFormat::Bc6hSigned => gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT,
+            BlockFormat::Bc7 => gl::COMPRESSED_RGBA_BPTC_UNORM,
+        }
+    }
+
+    fn is_supported(self, gl: &Gl) -> bool {
+        match self {
+            // RGTC is core since 3.0.
+            BlockFormat::Bc4 | BlockFormat::Bc5 => true,
+            BlockFormat::Bc1 | BlockFormat::Bc2 | BlockFormat::Bc3 => {
+                has_extension(gl, b"GL_EXT_texture_compression_s3tc")
+            }
+            _ => gl_version(gl) >= (4, 2) || has_extension(gl, b"GL_ARB_texture_compression_bptc"),
+        }
+    }
+}
+
+/// The size of mip level `level` of a `width` x `height` image.
+pub(super) fn level_size(width: u32, height: u32, level: usize) -> (u32, u32) {
+    let shrink = |size: u32| size.checked_shr(level as u32).unwrap_or(0).max(1);
+    (shrink(width), shrink(height))
+}
+
+/// The length of the full mip chain of a `width` x `height` image, down to 1x1.
+pub(super) fn max_levels(width: u32, height: u32) -> usize {
+    (32 - width.max(height).leading_zeros()) as usize
+}
+
+/// How the pixels of a texture file are stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Encoding {
+    Rgba8,
+    Bgra8,
+    Blocks(BlockFormat),
+}
+
+impl Encoding {
+    /// `None` when the size does not fit in memory, which only a corrupt header asks for.
+    pub(super) fn level_bytes(self, width: u32, height: u32, level: usize) -> Option<usize> {
+        let (width, height) = level_size(width, height, level);
+        match self {
+            Encoding::Rgba8 | Encoding::Bgra8 => (width as usize)
+                .checked_mul(height as usize)?
+                .checked_mul(4),
+            Encoding::Blocks(block) => {
+                let blocks = |pixels: u32| (pixels as usize).div_ceil(4);
+                blocks(width)
+                    .checked_mul(blocks(height))?
+                    .checked_mul(block.block_bytes())
+            }
+        }
+    }
+}
+
+/// A texture file read into memory, with the levels from the full size image down.
+pub(super) struct Levels {
+    pub(super) encoding: Encoding,
+    pub(super) width: u32,
+    pub(super) height: u32,
+    pub(super) levels: Vec<Vec<u8>>,
+}
+
+impl Texture {
+    /// Loads a DDS file with every mip level in it. Block compressed images stay compressed
+    /// where the GPU supports their format, elsewhere BC1 to BC5 are decompressed to RGBA8.
+    /// Files without mip levels get generated ones when uncompressed.
+    pub fn from_dds(gl: gl::Gl, fname: &Path, options: TextureOptions) -> Result<Self, Error> {
+        let bytes = std::fs::read(fname).map_err(|e| Error::io(fname, e))?;
+        let levels = parse_dds(fname, &bytes)?;
+        Self::from_file_levels(gl, fname, levels, options)
+    }
+
+    /// Loads a KTX2 file like `from_dds`. Supercompressed files, e.g. Basis Universal,
+    /// are not supported.
+    pub fn from_ktx2(gl: gl::Gl, fname: &Path, options: TextureOptions) -> Result<Self, Error> {
+        let bytes = std::fs::read(fname).map_err(|e| Error::io(fname, e))?;
+        let levels = parse_ktx2(fname, &bytes)?;
+        Self::from_file_levels(gl, fname, levels, options)
+    }
+
+    fn from_file_levels(
+        gl: gl::Gl,
+        fname: &Path,
+        file: Levels,
+        options: TextureOptions,
+    ) -> Result<Self, Error> {
+        let Levels {
+            encoding,
+            width,
+            height,
+            mut levels,
+        } = file;
+        match encoding {
+            Encoding::Rgba8 => {}
+            Encoding::Bgra8 => {
+                for level in levels.iter_mut() {
+                    level.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+                }
+            }
+            Encoding::Blocks(block) if block.is_supported(&gl) => {
+                let texture = Self::from_levels(gl, width, height, levels, Some(block), options);
+                return Ok(texture);
+            }
+            Encoding::Blocks(block) if block.can_decompress() => {
+                levels = levels
+                    .iter()
+                    .enumerate()
+                    .map(|(level, blocks)| {
+                        let (width, height) = level_size(width, height, level);
+                        decompress(block, width, height, blocks)
+                    })
+                    .collect();
+            }
+            Encoding::Blocks(block) => {
+                return Err(Error::invalid_data(
+                    fname,
+                    format!(
+                        "{:?} is not supported by the driver and cannot be decompressed",
+                        block
+                    ),
+                ));
+            }
+        }
+        Ok(Self::from_levels(gl, width, height, levels, None, options))
+    }
+}
+
+/// RGBA8 pixels out of the blocks of a `width` x `height` image.
+fn decompress(format: BlockFormat, width: u32, height: u32, blocks: &[u8]) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_wide = width.div_ceil(4);
+    let mut pixels = vec![0; width * height * 4];
+    for (index, block) in blocks.chunks_exact(format.block_bytes()).enumerate() {
+        let (block_x, block_y) = (index % blocks_wide * 4, index / blocks_wide * 4);
+        for (i, texel) in decode_block(format, block).iter().enumerate() {
+            let (x, y) = (block_x + i % 4, block_y + i / 4);
+            if x < width && y < height {
+                let start = (y * width + x) * 4;
+                pixels[start..start + 4].copy_from_slice(texel);
+            }
+        }
+    }
+    pixels
+}
+
+/// The 16 pixels of a block, row by row from the top left corner.
+fn decode_block(format: BlockFormat, block: &[u8]) -> [[u8; 4]; 16] {
+    match format {
+        BlockFormat::Bc1 => decode_colors(block, false),
+        BlockFormat::Bc2 => {
+            let mut texels = decode_colors(&block[8..], true);
+            let alphas = u64::from_le_bytes(block[..8].try_into().unwrap());
+            for (i, texel) in texels.iter_mut().enumerate() {
+                texel[3] = ((alphas >> (4 * i)) & 15) as u8 * 17;
+            }
+            texels
+        }
+        BlockFormat::Bc3 => {
+            let mut texels = decode_colors(&block[8..], true);
+            for (texel, alpha) in texels.iter_mut().zip(decode_channel(&block[..8]).iter()) {
+                texel[3] = *alpha;
+            }
+            texels
+        }
+        BlockFormat::Bc4 => {
+            let mut texels = [[0, 0, 0, 255]; 16];
+            for (texel, red) in texels.iter_mut().zip(decode_channel(block).iter()) {
+                texel[0] = *red;
+            }
+            texels
+        }
+        BlockFormat::Bc5 => {
+            let (red, green) = (decode_channel(&block[..8]), decode_channel(&block[8..]));
+            let mut texels = [[0, 0, 0, 255]; 16];
+            for (i, texel) in texels.iter_mut().enumerate() {
+                texel[0] = red[i];
+                texel[1] = green[i];
+            }
+            texels
+        }
+        _ => unreachable!("{:?} cannot be decompressed", format),
+    }
+}
+
+fn color_565(color: u16) -> [u32; 3] {
+    let (r, g, b) = (
+        (color >> 11) as u32,
+        (color >> 5 & 63) as u32,
+        (color & 31) as u32,
+    );
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+/// A BC1 block, or the color half of BC2 and BC3, which always use four colors.
+fn decode_colors(block: &[u8], four_colors: bool) -> [[u8; 4]; 16] {
+    let first = u16::from_le_bytes([block[0], block[1]]);
+    let second = u16::from_le_bytes([block[2], block[3]]);
+    let (a, b) = (color_565(first), color_565(second));
+    let mix = |weight_a: u32, weight_b: u32| {
+        let total = weight_a + weight_b;
+        let channel = |i: usize| ((a[i] * weight_a + b[i] * weight_b) / total) as u8;
+        [channel(0), channel(1), channel(2), 255]
+    };
+    let palette = if first > second || four_colors {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0, 0, 0, 0]]
+    };
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[(indices >> (2 * i) & 3) as usize];
+    }
+    texels
+}
+
+/// A BC4 block, or the alpha half of BC3.
+fn decode_channel(block: &[u8]) -> [u8; 16] {
+    let (a, b) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a as u8, b as u8, 0, 0, 0, 0, 0, 255];
+    if a > b {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a + i as u32 * b) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a + i as u32 * b) / 5) as u8;
+        }
+    }
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[(indices >> (3 * i) & 7) as usize];
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoding() {
+        // Red and blue, the first row picking each of the four colors.
+        let bc1 = [0x00, 0xF8, 0x1F, 0x00, 0b11_10_01_00, 0, 0, 0];
+        let texels = decode_block(BlockFormat::Bc1, &bc1);
+        assert_eq!(texels[0], [255, 0, 0, 255]);
+        assert_eq!(texels[1], [0, 0, 255, 255]);
+        assert_eq!(texels[2], [170, 0, 85, 255]);
+        assert_eq!(texels[3], [85, 0, 170, 255]);
+        assert_eq!(texels[4], [255, 0, 0, 255]);
+
+        // With the colors the other way around the last one is transparent.
+        let bc1 = [0x1F, 0x00, 0x00, 0xF8, 0b11_10_01_00, 0, 0, 0];
+        let texels = decode_block(BlockFormat::Bc1, &bc1);
+        assert_eq!(texels[2], [127, 0, 127, 255]);
+        assert_eq!(texels[3], [0, 0, 0, 0]);
+
+        let bc4 = [255, 0, 0b10_001_000, 0, 0, 0, 0, 0];
+        let reds: Vec<u8> = decode_block(BlockFormat::Bc4, &bc4)[..3]
+            .iter()
+            .map(|texel| texel[0])
+            .collect();
+        assert_eq!(reds, vec![255, 0, 218]);
+
+        // A 2x2 image is the top left corner of one block.
+        let pixels = decompress(
+            BlockFormat::Bc1,
+            2,
+            2,
+            &[0x00, 0xF8, 0x1F, 0x00, 0, 0, 0, 0],
+        );
+        assert_eq!(pixels, [255, 0, 0, 255].repeat(4));
+        assert_eq!(
+            Encoding::Blocks(BlockFormat::Bc3).level_bytes(10, 6, 0),
+            Some(3 * 2 * 16)
+        );
+        assert_eq!(
+            Encoding::Blocks(BlockFormat::Bc1).level_bytes(10, 6, 3),
+            Some(8)
+        );
+        assert_eq!(Encoding::Rgba8.level_bytes(u32::MAX, u32::MAX, 0), None);
+        assert_eq!(max_levels(10, 6), 4);
+        assert_eq!(max_levels(1, 1), 1);
+    }
+}
@@ -0,0 +1,180 @@
+use cgmath::{InnerSpace, Vector3};
+use image::{Rgba, RgbaImage};
+use std::{f32::consts::PI, path::Path};
+
+use super::{textures::PixelFormat, Error, Texture, TextureOptions, Wrap};
+
+/// The faces of a cubemap in the order they are given: +X, -X, +Y, -Y, +Z, -Z.
+/// Images are seen from the inside of the cube, with their top row towards +Y,
+/// or towards -Z and +Z for the top and bottom faces.
+pub const CUBE_FACES: [&str; 6] = ["+X", "-X", "+Y", "-Y", "+Z", "-Z"];
+
+impl Texture {
+    /// A cubemap out of six square images of one size, in the order of `CUBE_FACES`.
+    /// Wrapping is switched to clamping, so the edges of the faces do not bleed into each other.
+    pub fn cubemap_from_rgba8(gl: gl::Gl, faces: &[RgbaImage], options: TextureOptions) -> Self {
+        assert_eq!(faces.len(), 6, "A cubemap needs six faces");
+        let size = faces[0].width();
+        for (face, image) in CUBE_FACES.iter().zip(faces.iter()) {
+            assert_eq!(
+                image.dimensions(),
+                (size, size),
+                "The {} face is {}x{}, faces have to be {}x{}",
+                face,
+                image.width(),
+                image.height(),
+                size,
+                size
+            );
+        }
+        let bytes = faces
+            .iter()
+            .flat_map(|face| face.as_raw())
+            .copied()
+            .collect();
+        Self::upload(
+            gl,
+            gl::TEXTURE_CUBE_MAP,
+            (size, size, 6),
+            PixelFormat::Rgba8,
+            bytes,
+            options.with_wrap(Wrap::ClampToEdge),
+        )
+    }
+
+    /// Loads a cubemap from six files, in the order of `CUBE_FACES`.
+    pub fn cubemap_from_files(
+        gl: gl::Gl,
+        files: &[&Path; 6],
+        options: TextureOptions,
+    ) -> Result<Self, Error> {
+        let mut faces = vec![];
+        for fname in files.iter() {
+            let image = open_rgba8(fname)?;
+            let size = faces.first().map_or(image.width(), RgbaImage::width);
+            if image.dimensions() != (size, size) {
+                return Err(Error::invalid_data(
+                    fname,
+                    format!(
+                        "the face is {}x{}, faces have to be {}x{}",
+                        image.width(),
+                        image.height(),
+                        size,
+                        size
+                    ),
+                ));
+            }
+            faces.push(image);
+        }
+        Ok(Self::cubemap_from_rgba8(gl, &faces, options))
+    }
+
+    /// Loads a 360° panorama in the equirectangular projection, twice as wide as it is tall
+    /// with the middle column looking towards -Z, and turns it into a cubemap.
+    /// Faces are a quarter of the panorama's width.
+    pub fn cubemap_from_equirectangular(
+        gl: gl::Gl,
+        fname: &Path,
+        options: TextureOptions,
+    ) -> Result<Self, Error> {
+        let panorama = open_rgba8(fname)?;
+        let size = (panorama.width() / 4).max(1);
+        let faces = equirectangular_to_faces(&panorama, size);
+        Ok(Self::cubemap_from_rgba8(gl, &faces, options))
+    }
+}
+
+fn open_rgba8(fname: &Path) -> Result<RgbaImage, Error> {
+    Ok(image::open(fname)
+        .map_err(|source| Error::Image {
+            path: fname.to_path_buf(),
+            source,
+        })?
+        .into_rgba8())
+}
+
+/// The direction from the center of the cube through a point of a face,
+/// `s` and `t` going from -1 to 1 left to right and top to bottom.
+fn face_direction(face: usize, s: f32, t: f32) -> Vector3<f32> {
+    match face {
+        0 => Vector3::new(1.0, -t, -s),
+        1 => Vector3::new(-1.0, -t, s),
+        2 => Vector3::new(s, 1.0, t),
+        3 => Vector3::new(s, -1.0, -t),
+        4 => Vector3::new(s, -t, 1.0),
+        _ => Vector3::new(-s, -t, -1.0),
+    }
+}
+
+/// Projects the panorama on the six faces of a cube, each `size` x `size`.
+fn equirectangular_to_faces(panorama: &RgbaImage, size: u32) -> Vec<RgbaImage> {
+    (0..6)
+        .map(|face| {
+            RgbaImage::from_fn(size, size, |x, y| {
+                let s = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                let t = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                let direction = face_direction(face, s, t).normalize();
+                let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * PI);
+                let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+                sample_bilinear(panorama, u, v)
+            })
+        })
+        .collect()
+}
+
+/// Samples at `u`, `v` from 0 to 1, repeating horizontally and clamping vertically.
+fn sample_bilinear(image: &RgbaImage, u: f32, v: f32) -> Rgba<u8> {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let pixel = |dx: i64, dy: i64| {
+        let px = (x0 as i64 + dx).rem_euclid(width) as u32;
+        let py = (y0 as i64 + dy).min(height - 1) as u32;
+        image.get_pixel(px, py).0
+    };
+    let (a, b, c, d) = (pixel(0, 0), pixel(1, 0), pixel(0, 1), pixel(1, 1));
+    let mut result = [0; 4];
+    for (i, channel) in result.iter_mut().enumerate() {
+        let top = a[i] as f32 * (1.0 - fx) + b[i] as f32 * fx;
+        let bottom = c[i] as f32 * (1.0 - fx) + d[i] as f32 * fx;
+        *channel = (top * (1.0 - fy) + bottom * fy).round() as u8;
+    }
+    Rgba(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panorama_faces() {
+        // Sky above the horizon, ground below, and the half looking towards -Z marked red.
+        let panorama = RgbaImage::from_fn(64, 32, |x, y| {
+            let red = if (16..48).contains(&x) { 255 } else { 0 };
+            let blue = if y < 16 { 255 } else { 0 };
+            Rgba([red, 0, blue, 255])
+        });
+        let faces = equirectangular_to_faces(&panorama, 8);
+        assert_eq!(faces.len(), 6);
+        assert!(faces.iter().all(|face| face.dimensions() == (8, 8)));
+
+        let center = |face: usize, y: u32| faces[face].get_pixel(4, y).0;
+        assert_eq!(center(2, 4)[2], 255);
+        assert_eq!(center(3, 4)[2], 0);
+        assert_eq!(center(5, 1), [255, 0, 255, 255]);
+        assert_eq!(center(5, 6), [255, 0, 0, 255]);
+        assert_eq!(center(4, 1), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn face_directions() {
+        // The top row of the side faces looks up, the top row of +Y looks towards -Z.
+        for face in [0, 1, 4, 5] {
+            assert!(face_direction(face, 0.0, -1.0).y > 0.0);
+        }
+        assert_eq!(face_direction(2, 0.0, -1.0), Vector3::new(0.0, 1.0, -1.0));
+        assert_eq!(face_direction(4, 1.0, 0.0), Vector3::new(1.0, 0.0, 1.0));
+    }
+}
@@ -0,0 +1,207 @@
+use std::{convert::TryInto, path::Path};
+
+use super::{
+    compression::{max_levels, BlockFormat, Encoding, Levels},
+    Error,
+};
+
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+
+const MIPMAP_COUNT_FLAG: u32 = 0x2_0000;
+const FOURCC_FLAG: u32 = 0x4;
+const RGB_FLAG: u32 = 0x40;
+const CUBEMAP_FLAG: u32 = 0x200;
+const VOLUME_FLAG: u32 = 0x20_0000;
+
+/// Reads a 2D DDS file, block compressed or with 32 bit RGBA pixels.
+pub(super) fn parse_dds(file_name: &Path, bytes: &[u8]) -> Result<Levels, Error> {
+    let error = |message: &str| Error::invalid_data(file_name, message.to_string());
+    if bytes.len() < HEADER_SIZE || !bytes.starts_with(b"DDS ") {
+        return Err(error("Not a DDS file"));
+    }
+    let int = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let (height, width) = (int(12), int(16));
+    if width == 0 || height == 0 {
+        return Err(error("The image is empty"));
+    }
+    if int(112) & (CUBEMAP_FLAG | VOLUME_FLAG) != 0 {
+        return Err(error("Cubemaps and volume textures are not supported"));
+    }
+    let level_count = if int(8) & MIPMAP_COUNT_FLAG != 0 {
+        int(28).max(1) as usize
+    } else {
+        1
+    };
+    if level_count > max_levels(width, height) {
+        return Err(Error::invalid_data(
+            file_name,
+            format!(
+                "{} mip levels are more than a {}x{} image has",
+                level_count, width, height
+            ),
+        ));
+    }
+
+    let pixel_flags = int(80);
+    let (encoding, data_start) = if pixel_flags & FOURCC_FLAG != 0 {
+        let fourcc = &bytes[84..88];
+        if fourcc == b"DX10" {
+            if bytes.len() < HEADER_SIZE + DX10_HEADER_SIZE {
+                return Err(error("The DX10 header is cut off"));
+            }
+            if int(HEADER_SIZE + 12) > 1 {
+                return Err(error("Texture arrays are not supported"));
+            }
+            let dxgi_format = int(HEADER_SIZE);
+            let encoding = dxgi_encoding(dxgi_format).ok_or_else(|| {
+                Error::invalid_data(
+                    file_name,
+                    format!("DXGI format {} is not supported", dxgi_format),
+                )
+            })?;
+            (encoding, HEADER_SIZE + DX10_HEADER_SIZE)
+        } else {
+            let encoding = fourcc_encoding(fourcc).ok_or_else(|| {
+                Error::invalid_data(
+                    file_name,
+                    format!(
+                        "Format `{}` is not supported",
+                        String::from_utf8_lossy(fourcc)
+                    ),
+                )
+            })?;
+            (encoding, HEADER_SIZE)
+        }
+    } else if pixel_flags & RGB_FLAG != 0 && int(88) == 32 {
+        match (int(92), int(96), int(100), int(104)) {
+            (0xFF, 0xFF00, 0xFF_0000, 0xFF00_0000) => (Encoding::Rgba8, HEADER_SIZE),
+            (0xFF_0000, 0xFF00, 0xFF, 0xFF00_0000) => (Encoding::Bgra8, HEADER_SIZE),
+            _ => return Err(error("Only RGBA and BGRA 32 bit pixels are supported")),
+        }
+    } else {
+        return Err(error(
+            "Only block compressed and 32 bit RGBA pixels are supported",
+        ));
+    };
+
+    // The levels follow each other, from the full size image down.
+    let mut levels = Vec::with_capacity(level_count);
+    let mut offset = data_start;
+    for level in 0..level_count {
+        let size = encoding.level_bytes(width, height, level).ok_or_else(|| {
+            Error::invalid_data(file_name, format!("Mip level {} is too large", level))
+        })?;
+        match bytes.get(offset..offset.saturating_add(size)) {
+            Some(data) => levels.push(data.to_vec()),
+            None => {
+                return Err(Error::invalid_data(
+                    file_name,
+                    format!("The file ends before mip level {}", level),
+                ))
+            }
+        }
+        offset += size;
+    }
+    Ok(Levels {
+        encoding,
+        width,
+        height,
+        levels,
+    })
+}
+
+fn fourcc_encoding(fourcc: &[u8]) -> Option<Encoding> {
+    let block = match fourcc {
+        b"DXT1" => BlockFormat::Bc1,
+        // Premultiplied alpha is loaded as it is.
+        b"DXT2" | b"DXT3" => BlockFormat::Bc2,
+        b"DXT4" | b"DXT5" => BlockFormat::Bc3,
+        b"ATI1" | b"BC4U" => BlockFormat::Bc4,
+        b"ATI2" | b"BC5U" => BlockFormat::Bc5,
+        _ => return None,
+    };
+    Some(Encoding::Blocks(block))
+}
+
+fn dxgi_encoding(format: u32) -> Option<Encoding> {
+    let block = match format {
+        28 | 29 => return Some(Encoding::Rgba8),
+        87 | 91 => return Some(Encoding::Bgra8),
+        71 | 72 => BlockFormat::Bc1,
+        74 | 75 => BlockFormat::Bc2,
+        77 | 78 => BlockFormat::Bc3,
+        80 => BlockFormat::Bc4,
+        83 => BlockFormat::Bc5,
+        95 => BlockFormat::Bc6hUnsigned,
+        96 => BlockFormat::Bc6hSigned,
+        98 | 99 => BlockFormat::Bc7,
+        _ => return None,
+    };
+    Some(Encoding::Blocks(block))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(width: u32, height: u32, levels: u32, fourcc: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = vec![0; HEADER_SIZE];
+        bytes[..4].copy_from_slice(b"DDS ");
+        let mut set = |offset: usize, value: u32| {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        set(4, 124);
+        set(8, MIPMAP_COUNT_FLAG);
+        set(12, height);
+        set(16, width);
+        set(28, levels);
+        set(76, 32);
+        set(80, FOURCC_FLAG);
+        bytes[84..88].copy_from_slice(fourcc);
+        bytes
+    }
+
+    #[test]
+    fn mip_levels() {
+        let path = Path::new("test.dds");
+        // 8x4, 4x2, 2x1 and 1x1 all take a whole block or two.
+        let mut bytes = header(8, 4, 4, b"DXT5");
+        bytes.extend(vec![1; 2 * 16]);
+        bytes.extend(vec![2; 3 * 16]);
+        let file = parse_dds(path, &bytes).unwrap();
+        assert_eq!(file.encoding, Encoding::Blocks(BlockFormat::Bc3));
+        assert_eq!((file.width, file.height), (8, 4));
+        let sizes: Vec<usize> = file.levels.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![32, 16, 16, 16]);
+        assert_eq!(file.levels[0], vec![1; 32]);
+
+        bytes.truncate(bytes.len() - 1);
+        let error = parse_dds(path, &bytes).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "test.dds: The file ends before mip level 3"
+        );
+
+        let error = parse_dds(path, &header(8, 4, 5, b"DXT5")).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "test.dds: 5 mip levels are more than a 8x4 image has"
+        );
+
+        let mut huge = header(u32::MAX, u32::MAX, 1, b"DXT5");
+        huge[80..84].copy_from_slice(&RGB_FLAG.to_le_bytes());
+        huge[88..92].copy_from_slice(&32u32.to_le_bytes());
+        for (i, mask) in [0xFFu32, 0xFF00, 0xFF_0000, 0xFF00_0000].iter().enumerate() {
+            huge[92 + i * 4..96 + i * 4].copy_from_slice(&mask.to_le_bytes());
+        }
+        let error = parse_dds(path, &huge).err().unwrap();
+        assert_eq!(error.to_string(), "test.dds: Mip level 0 is too large");
+
+        let error = parse_dds(path, &header(4, 4, 1, b"ETC1")).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "test.dds: Format `ETC1` is not supported"
+        );
+    }
+}
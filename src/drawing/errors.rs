@@ -0,0 +1,137 @@
+use gl::types::GLenum;
+use std::{fmt, path::PathBuf};
+
+/// Everything that can go wrong while loading models, textures, shaders and programs,
+/// or while setting up render targets.
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    /// An image in memory that could not be decoded.
+    ImageDecode {
+        source: image::ImageError,
+    },
+    /// An image with no pixels, added to an atlas under `name`.
+    EmptyImage {
+        name: String,
+    },
+    Gltf {
+        path: PathBuf,
+        source: gltf::Error,
+    },
+    /// `line` starts from 1.
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// Broken binary data, where there are no lines to point at.
+    InvalidData {
+        path: PathBuf,
+        message: String,
+    },
+    ShaderCompile {
+        kind: GLenum,
+        log: String,
+    },
+    ProgramLink {
+        log: String,
+    },
+    MissingUniform {
+        name: String,
+    },
+    /// `status` is what `CheckFramebufferStatus` returned.
+    IncompleteFramebuffer {
+        status: GLenum,
+    },
+}
+
+impl Error {
+    pub(super) fn io(path: &std::path::Path, source: std::io::Error) -> Self {
+        Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub(super) fn invalid_data(path: &std::path::Path, message: String) -> Self {
+        Error::InvalidData {
+            path: path.to_path_buf(),
+            message,
+        }
+    }
+
+    pub(super) fn parse(path: &std::path::Path, line: usize, message: String) -> Self {
+        Error::Parse {
+            path: path.to_path_buf(),
+            line,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => {
+                write!(f, "Could not read {}: {}", path.display(), source)
+            }
+            Error::Image { path, source } => {
+                write!(f, "Could not load image {}: {}", path.display(), source)
+            }
+            Error::ImageDecode { source } => write!(f, "Could not decode image: {}", source),
+            Error::EmptyImage { name } => write!(f, "Image {} has no pixels", name),
+            Error::Gltf { path, source } => {
+                write!(f, "Could not load glTF {}: {}", path.display(), source)
+            }
+            Error::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Error::InvalidData { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::ShaderCompile { kind, log } => {
+                let kind = match *kind {
+                    gl::VERTEX_SHADER => "vertex",
+                    gl::FRAGMENT_SHADER => "fragment",
+                    gl::GEOMETRY_SHADER => "geometry",
+                    gl::COMPUTE_SHADER => "compute",
+                    _ => "unknown",
+                };
+                write!(f, "Could not compile {} shader: {}", kind, log)
+            }
+            Error::ProgramLink { log } => write!(f, "Could not link program: {}", log),
+            Error::MissingUniform { name } => {
+                write!(f, "Could not find uniform {} in program", name)
+            }
+            Error::IncompleteFramebuffer { status } => {
+                let reason = match *status {
+                    gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "an attachment is incomplete",
+                    gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "there are no attachments",
+                    gl::FRAMEBUFFER_UNSUPPORTED => "the formats are not supported together",
+                    gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "the sample counts differ",
+                    _ => "unknown reason",
+                };
+                write!(f, "Framebuffer is incomplete: {} ({:#x})", reason, status)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            Error::ImageDecode { source } => Some(source),
+            Error::Gltf { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::{convert::TryInto, path::Path};
+
+use super::{
+    compression::{max_levels, BlockFormat, Encoding, Levels},
+    Error,
+};
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+/// Reads a 2D KTX2 file, block compressed or with 32 bit RGBA pixels.
+pub(super) fn parse_ktx2(file_name: &Path, bytes: &[u8]) -> Result<Levels, Error> {
+    let error = |message: &str| Error::invalid_data(file_name, message.to_string());
+    if bytes.len() < HEADER_SIZE || !bytes.starts_with(&IDENTIFIER) {
+        return Err(error("Not a KTX2 file"));
+    }
+    let int = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let long =
+        |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap()) as usize;
+
+    let vk_format = int(12);
+    let (width, height) = (int(20), int(24).max(1));
+    if width == 0 {
+        return Err(error("The image is empty"));
+    }
+    if int(28) > 1 || int(32) > 1 || int(36) != 1 {
+        return Err(error(
+            "3D textures, texture arrays and cubemaps are not supported",
+        ));
+    }
+    if int(44) != 0 {
+        return Err(error("Supercompressed files are not supported"));
+    }
+    let encoding = vk_encoding(vk_format).ok_or_else(|| {
+        Error::invalid_data(
+            file_name,
+            format!("Vulkan format {} is not supported", vk_format),
+        )
+    })?;
+
+    // 0 asks for the levels to be generated.
+    let level_count = int(40).max(1) as usize;
+    if level_count > max_levels(width, height) {
+        return Err(Error::invalid_data(
+            file_name,
+            format!(
+                "{} mip levels are more than a {}x{} image has",
+                level_count, width, height
+            ),
+        ));
+    }
+    if bytes.len() < HEADER_SIZE + level_count * LEVEL_INDEX_ENTRY_SIZE {
+        return Err(error("The level index is cut off"));
+    }
+    let mut levels = Vec::with_capacity(level_count);
+    for level in 0..level_count {
+        let entry = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+        let (offset, length) = (long(entry), long(entry + 8));
+        let size = encoding.level_bytes(width, height, level).ok_or_else(|| {
+            Error::invalid_data(file_name, format!("Mip level {} is too large", level))
+        })?;
+        if length != size {
+            return Err(Error::invalid_data(
+                file_name,
+                format!(
+                    "Mip level {} takes {} bytes instead of {}",
+                    level, length, size
+                ),
+            ));
+        }
+        match bytes.get(offset..offset.saturating_add(length)) {
+            Some(data) => levels.push(data.to_vec()),
+            None => {
+                return Err(Error::invalid_data(
+                    file_name,
+                    format!("Mip level {} is outside of the file", level),
+                ))
+            }
+        }
+    }
+    Ok(Levels {
+        encoding,
+        width,
+        height,
+        levels,
+    })
+}
+
+fn vk_encoding(format: u32) -> Option<Encoding> {
+    let block = match format {
+        37 | 43 => return Some(Encoding::Rgba8),
+        44 | 50 => return Some(Encoding::Bgra8),
+        131..=134 => BlockFormat::Bc1,
+        135 | 136 => BlockFormat::Bc2,
+        137 | 138 => BlockFormat::Bc3,
+        139 => BlockFormat::Bc4,
+        141 => BlockFormat::Bc5,
+        143 => BlockFormat::Bc6hUnsigned,
+        144 => BlockFormat::Bc6hSigned,
+        145 | 146 => BlockFormat::Bc7,
+        _ => return None,
+    };
+    Some(Encoding::Blocks(block))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_index() {
+        // A 4x4 BC1 image with its 2x2 and 1x1 levels, stored smallest first.
+        let mut bytes = IDENTIFIER.to_vec();
+        for value in [131u32, 1, 4, 4, 0, 0, 1, 3, 0, 0, 0, 0, 0] {
+            bytes.extend(&value.to_le_bytes());
+        }
+        bytes.resize(HEADER_SIZE, 0);
+        let data_start = (HEADER_SIZE + 3 * LEVEL_INDEX_ENTRY_SIZE) as u64;
+        for offset in [data_start + 16, data_start + 8, data_start] {
+            for value in [offset, 8, 8] {
+                bytes.extend(&value.to_le_bytes());
+            }
+        }
+        bytes.extend([3; 8].iter().chain([2; 8].iter()).chain([1; 8].iter()));
+
+        let path = Path::new("test.ktx2");
+        let file = parse_ktx2(path, &bytes).unwrap();
+        assert_eq!(file.encoding, Encoding::Blocks(BlockFormat::Bc1));
+        assert_eq!(file.levels, vec![vec![1; 8], vec![2; 8], vec![3; 8]]);
+
+        bytes[40] = 40;
+        let error = parse_ktx2(path, &bytes).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "test.ktx2: 40 mip levels are more than a 4x4 image has"
+        );
+
+        bytes[40] = 3;
+        bytes[44] = 2;
+        let error = parse_ktx2(path, &bytes).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "test.ktx2: Supercompressed files are not supported"
+        );
+    }
+}
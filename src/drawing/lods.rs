@@ -0,0 +1,102 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Rad};
+use std::rc::Rc;
+
+use super::{BoundingSphere, Camera, Model};
+
+/// When each level after the first one takes over. Thresholds go from the first switch to the last.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LodSelection {
+    /// Camera distances to the center of the bounding sphere, in world units, ascending.
+    Distance(Vec<f32>),
+    /// Heights of the bounding sphere on screen as a fraction of the screen height, descending.
+    ScreenSize(Vec<f32>),
+}
+
+impl LodSelection {
+    fn level(&self, sphere: &BoundingSphere, camera: &Camera, field_of_view: Rad<f32>) -> usize {
+        let distance = (sphere.center - camera.origin.to_vec()).magnitude();
+        match self {
+            LodSelection::Distance(thresholds) => {
+                thresholds.iter().filter(|t| distance >= **t).count()
+            }
+            LodSelection::ScreenSize(thresholds) => {
+                if distance <= sphere.radius {
+                    return 0;
+                }
+                let size = sphere.radius / (distance * (field_of_view.0 / 2.0).tan());
+                thresholds.iter().filter(|t| size < **t).count()
+            }
+        }
+    }
+}
+
+/// The same model in decreasing levels of detail.
+pub struct Lods {
+    levels: Vec<Rc<Model>>,
+    selection: LodSelection,
+}
+
+impl Lods {
+    /// `levels` go from the most detailed one and must not be empty.
+    pub fn new(levels: Vec<Rc<Model>>, selection: LodSelection) -> Self {
+        assert!(!levels.is_empty(), "There has to be at least one level");
+        Self { levels, selection }
+    }
+
+    /// Builds up to `count` levels from `model`, each with about half the triangles of the previous one.
+    /// Returns `None` when the model did not keep its geometry, see `Model::simplified`.
+    pub fn simplified(
+        gl: gl::Gl,
+        model: Rc<Model>,
+        count: usize,
+        selection: LodSelection,
+    ) -> Option<Self> {
+        let mut levels = vec![model];
+        while levels.len() < count {
+            let next = levels.last().unwrap().simplified(gl.clone(), 0.5)?;
+            levels.push(Rc::new(next));
+        }
+        Some(Self::new(levels, selection))
+    }
+
+    pub fn levels(&self) -> &[Rc<Model>] {
+        &self.levels
+    }
+
+    /// The level to draw the model placed by `matrix` with, as seen from `camera`.
+    pub fn select(&self, matrix: &Matrix4<f32>, camera: &Camera) -> &Rc<Model> {
+        let sphere = self.levels[0].bounding_sphere().transform(matrix);
+        let level = self
+            .selection
+            .level(&sphere, camera, camera.field_of_view());
+        &self.levels[level.min(self.levels.len() - 1)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Point3, Vector3};
+
+    #[test]
+    fn selection() {
+        let camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0), 1.0);
+        let sphere = |distance: f32| BoundingSphere {
+            center: Vector3::new(distance, 0.0, 0.0),
+            radius: 1.0,
+        };
+        let fov = Rad(std::f32::consts::FRAC_PI_2);
+
+        let distance = LodSelection::Distance(vec![10.0, 20.0]);
+        assert_eq!(distance.level(&sphere(5.0), &camera, fov), 0);
+        assert_eq!(distance.level(&sphere(15.0), &camera, fov), 1);
+        assert_eq!(distance.level(&sphere(50.0), &camera, fov), 2);
+
+        // With a right angle view a unit sphere at distance `d` covers `1 / d` of the screen.
+        let size = LodSelection::ScreenSize(vec![0.5, 0.1]);
+        assert_eq!(size.level(&sphere(0.5), &camera, fov), 0);
+        assert_eq!(size.level(&sphere(1.5), &camera, fov), 0);
+        assert_eq!(size.level(&sphere(4.0), &camera, fov), 1);
+        assert_eq!(size.level(&sphere(20.0), &camera, fov), 2);
+    }
+}
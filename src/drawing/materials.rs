@@ -0,0 +1,205 @@
+use cgmath::Vector3;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use super::{parse, Error, Program, Texture, UniformResult};
+
+pub struct Material {
+    pub name: String,
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    pub shininess: f32,
+    pub diffuse_map: Option<PathBuf>,
+    pub bump_map: Option<PathBuf>,
+    diffuse_texture: Option<Rc<Texture>>,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: (1.0, 1.0, 1.0).into(),
+            diffuse: (1.0, 1.0, 1.0).into(),
+            specular: (1.0, 1.0, 1.0).into(),
+            shininess: 32.0,
+            diffuse_map: None,
+            bump_map: None,
+            diffuse_texture: None,
+        }
+    }
+
+    /// Reads every material of an `.mtl` file. Texture paths are resolved against the file's directory.
+    pub fn from_mtl(file_name: &Path) -> Result<Vec<Self>, Error> {
+        let file = std::fs::read_to_string(file_name).map_err(|e| Error::io(file_name, e))?;
+        parse_mtl(file_name, file.lines())
+    }
+
+    pub fn load_textures(&mut self, gl: gl::Gl) -> Result<(), Error> {
+        if let Some(path) = &self.diffuse_map {
+            self.diffuse_texture = Some(Rc::new(Texture::new(gl, path)?));
+        }
+        Ok(())
+    }
+
+    pub fn diffuse_texture(&self) -> Option<&Rc<Texture>> {
+        self.diffuse_texture.as_ref()
+    }
+
+    pub(super) fn set_diffuse_texture(&mut self, texture: Rc<Texture>) {
+        self.diffuse_texture = Some(texture);
+    }
+
+    /// Sets the `name` struct of `program`, skipping the fields its shaders do not use.
+    pub fn uniform(&self, name: &str, program: &Program) -> Result<(), Error> {
+        program
+            .set_vec3(format!("{}.ambient", name).as_str(), self.ambient)
+            .or_stripped()?;
+        program
+            .set_vec3(format!("{}.diffuse", name).as_str(), self.diffuse)
+            .or_stripped()?;
+        program
+            .set_vec3(format!("{}.specular", name).as_str(), self.specular)
+            .or_stripped()?;
+        // `Ns 0` would light every pixel with the full specular color.
+        program
+            .set_float(
+                format!("{}.shininess", name).as_str(),
+                self.shininess.max(1.0),
+            )
+            .or_stripped()
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new("default")
+    }
+}
+
+fn parse_mtl<'a, I>(file_name: &Path, lines: I) -> Result<Vec<Material>, Error>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let base = file_name.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials: Vec<Material> = vec![];
+    for (number, line) in lines.enumerate() {
+        parse_mtl_line(line, base, &mut materials)
+            .map_err(|message| Error::parse(file_name, number + 1, message))?;
+    }
+    Ok(materials)
+}
+
+fn parse_mtl_line(line: &str, base: &Path, materials: &mut Vec<Material>) -> Result<(), String> {
+    let split: Vec<&str> = line.split_whitespace().collect();
+    if split.is_empty() || split[0].starts_with('#') {
+        return Ok(());
+    }
+    if split[0] == "newmtl" {
+        if split.len() < 2 {
+            return Err("Material without a name".to_string());
+        }
+        materials.push(Material::new(split[1]));
+        return Ok(());
+    }
+    let material = match materials.last_mut() {
+        Some(material) => material,
+        None => return Err(format!("`{}` before any `newmtl`", split[0])),
+    };
+    match split[0] {
+        "Ka" => material.ambient = parse_color(&split)?,
+        "Kd" => material.diffuse = parse_color(&split)?,
+        "Ks" => material.specular = parse_color(&split)?,
+        "Ns" if split.len() > 1 => material.shininess = parse::<f32>(split[1])?,
+        // Map statements may carry options such as `-bm 0.5`, the file name always comes last.
+        "map_Kd" if split.len() > 1 => {
+            material.diffuse_map = Some(base.join(split[split.len() - 1]))
+        }
+        "map_Bump" | "map_bump" | "bump" if split.len() > 1 => {
+            material.bump_map = Some(base.join(split[split.len() - 1]))
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn parse_color(split: &[&str]) -> Result<Vector3<f32>, String> {
+    match split.len() {
+        // A single value means a grey color.
+        2 => {
+            let value = parse::<f32>(split[1])?;
+            Ok((value, value, value).into())
+        }
+        n if n >= 4 => Ok((
+            parse::<f32>(split[1])?,
+            parse::<f32>(split[2])?,
+            parse::<f32>(split[3])?,
+        )
+            .into()),
+        _ => Err(format!("Could not parse color `{}`", split.join(" "))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Vec<Material>, Error> {
+        parse_mtl(Path::new("models/test.mtl"), source.lines())
+    }
+
+    #[test]
+    fn colors_and_maps() {
+        let materials = parse(
+            "# two materials
+newmtl red
+Ka 0.1 0.2 0.3
+Kd 1 0 0
+Ks 0.5
+Ns 96.5
+map_Kd -bm 0.5 textures/red.png
+
+newmtl bumpy
+bump bumps.png
+",
+        )
+        .unwrap();
+        assert_eq!(materials.len(), 2);
+        let red = &materials[0];
+        assert_eq!(red.name, "red");
+        assert_eq!(red.ambient, Vector3::new(0.1, 0.2, 0.3));
+        assert_eq!(red.diffuse, Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(red.specular, Vector3::new(0.5, 0.5, 0.5));
+        assert_eq!(red.shininess, 96.5);
+        assert_eq!(
+            red.diffuse_map.as_deref(),
+            Some(Path::new("models/textures/red.png"))
+        );
+        assert_eq!(red.bump_map, None);
+
+        let bumpy = &materials[1];
+        assert_eq!(bumpy.diffuse, Vector3::new(1.0, 1.0, 1.0));
+        assert_eq!(bumpy.shininess, 32.0);
+        assert_eq!(
+            bumpy.bump_map.as_deref(),
+            Some(Path::new("models/bumps.png"))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        match parse("newmtl red\nKd 1 x 0") {
+            Err(Error::Parse { path, line, .. }) => {
+                assert_eq!(path, Path::new("models/test.mtl"));
+                assert_eq!(line, 2);
+            }
+            _ => panic!("expected a parse error"),
+        }
+        assert!(parse("newmtl red\nNs shiny").is_err());
+        assert!(parse("newmtl red\nKd 1 0").is_err());
+        assert!(parse("newmtl").is_err());
+        assert!(parse("Kd 1 0 0").is_err());
+    }
+}
@@ -1,19 +1,103 @@
+mod animations;
+mod atlases;
+mod bounds;
+mod builders;
 mod camera;
+mod compression;
+mod cubemaps;
+mod dds;
+mod errors;
+mod ktx2;
+mod lods;
+mod materials;
 mod models;
+mod morphs;
+mod objects;
+mod ply;
+mod primitives;
 mod programs;
+mod render_targets;
+mod samplers;
+mod scenes;
 mod shaders;
+mod simplification;
+mod skyboxes;
+mod stl;
 mod textures;
+mod vertices;
 
 use std::ffi::CString;
 
+pub use animations::{
+    AnimationClip, Animator, Channel, Interpolation, Joint, JointTransform, Keyframes, Skeleton,
+    Skin, SkinnedVertex, MAX_JOINTS,
+};
+pub use atlases::{Atlas, AtlasBuilder, UvRect};
+pub use bounds::{Aabb, BoundingSphere};
+pub use builders::MeshBuilder;
 pub use camera::Camera;
-pub use models::Model;
-pub use programs::Program;
+pub use compression::BlockFormat;
+pub use cubemaps::CUBE_FACES;
+pub use errors::Error;
+pub use lods::{LodSelection, Lods};
+pub use materials::Material;
+pub use models::{Mesh, Model};
+pub use models::{MeshData, NormalGeneration, ObjOptions, VertexData};
+pub use morphs::{MorphAnimation, MorphAnimator, MorphVertex, MAX_MORPH_TARGETS};
+pub use objects::{
+    live_objects, report_leaks, Buffer, Framebuffer, ObjectKind, Renderbuffer, VertexArray,
+};
+pub use programs::{Program, UniformResult};
+pub use render_targets::{AttachmentFormat, RenderTarget, Storage};
+pub use samplers::{Filter, MipmapFilter, Sampler, TextureOptions, Wrap};
+pub use scenes::Scene;
+pub use scenes::SceneNode;
 pub use shaders::Shader;
+pub use skyboxes::Skybox;
 pub use textures::Texture;
+pub use textures::{PixelData, PixelFormat};
+pub use vertices::{set_layer, PositionVertex, TangentVertex, Vertex, VertexAttribute};
+pub(crate) use vertices::vertex_format;
+pub use vertices::{LayeredVertex, LAYER_LOCATION};
 
-pub(self) fn create_whitespace_cstring(len: usize) -> CString {
+fn create_whitespace_cstring(len: usize) -> CString {
     let mut buffer: Vec<u8> = Vec::with_capacity(len + 1);
     buffer.extend([b' '].iter().cycle().take(len));
     unsafe { CString::from_vec_unchecked(buffer) }
 }
+
+fn parse<T>(string: &str) -> Result<T, String>
+where
+    T: std::str::FromStr,
+{
+    string.parse::<T>().map_err(|_| {
+        format!(
+            "Could not parse `{}` into {}",
+            string,
+            std::any::type_name::<T>()
+        )
+    })
+}
+
+/// The version of the current context, e.g. `(4, 5)`.
+fn gl_version(gl: &gl::Gl) -> (i32, i32) {
+    let (mut major, mut minor) = (0, 0);
+    unsafe {
+        gl.GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl.GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    (major, minor)
+}
+
+/// Whether the driver has an extension, e.g. `b"GL_EXT_texture_compression_s3tc"`.
+fn has_extension(gl: &gl::Gl, name: &[u8]) -> bool {
+    let mut count = 0;
+    unsafe {
+        gl.GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+    }
+    (0..count as gl::types::GLuint).any(|i| {
+        let extension = unsafe { gl.GetStringi(gl::EXTENSIONS, i) };
+        !extension.is_null()
+            && unsafe { std::ffi::CStr::from_ptr(extension as *const _) }.to_bytes() == name
+    })
+}
@@ -1,7 +1,19 @@
+use cgmath::{InnerSpace, Rad, Vector3, Zero};
 use gl::{
-    types::{GLenum, GLint, GLsizeiptr, GLuint, GLvoid},
+    types::{GLenum, GLint, GLintptr, GLsizeiptr, GLvoid},
     Gl,
 };
+use std::{collections::HashMap, path::Path, rc::Rc};
+
+use super::{
+    bounds::{Aabb, BoundingSphere},
+    objects::{Buffer, VertexArray},
+    parse,
+    ply::parse_ply,
+    stl::parse_stl,
+    vertices::{make_vao, positions, Vertex, VertexAttribute},
+    Error, Material,
+};
 
 #[repr(C, packed)]
 #[derive(Clone, Copy)]
@@ -21,34 +33,12 @@ impl Into<Triple> for (f32, f32, f32) {
     }
 }
 
-impl Triple {
-    fn attach_to_vao(gl: Gl, vec: &Vec<Self>, vao: GLuint, index: GLuint) {
-        let mut vbo = 0;
-        unsafe {
-            gl.GenBuffers(1, &mut vbo);
-            gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
-
-            gl.BufferData(
-                gl::ARRAY_BUFFER,
-                (vec.len() * std::mem::size_of::<Triple>()) as GLsizeiptr,
-                vec.as_ptr() as *const GLvoid,
-                gl::STATIC_DRAW,
-            );
-
-            gl.BindVertexArray(vao);
-
-            gl.EnableVertexAttribArray(index);
-            gl.VertexAttribPointer(
-                index,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                (std::mem::size_of::<Triple>()) as GLint,
-                std::ptr::null(),
-            );
-
-            gl.BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl.BindVertexArray(0);
+impl From<Vector3<f32>> for Triple {
+    fn from(vector: Vector3<f32>) -> Self {
+        Triple {
+            x: vector.x,
+            y: vector.y,
+            z: vector.z,
         }
     }
 }
@@ -69,37 +59,10 @@ impl Into<Double> for (f32, f32) {
     }
 }
 
-impl Double {
-    fn attach_to_vao(gl: Gl, vec: &Vec<Self>, vao: GLuint, index: GLuint) {
-        let mut vbo = 0;
-        unsafe {
-            gl.GenBuffers(1, &mut vbo);
-            gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
-
-            gl.BufferData(
-                gl::ARRAY_BUFFER,
-                (vec.len() * std::mem::size_of::<Self>()) as GLsizeiptr,
-                vec.as_ptr() as *const GLvoid,
-                gl::STATIC_DRAW,
-            );
-
-            gl.BindVertexArray(vao);
-
-            gl.EnableVertexAttribArray(index);
-            gl.VertexAttribPointer(
-                index,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                (std::mem::size_of::<Self>()) as GLint,
-                std::ptr::null(),
-            );
-            gl.BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl.BindVertexArray(0);
-        }
-    }
-}
+const TRIPLE: usize = std::mem::size_of::<Triple>();
+const DOUBLE: usize = std::mem::size_of::<Double>();
 
+/// Attributes are interleaved in this order, see `ATTRIBUTES` for their shader locations.
 #[repr(C, packed)]
 #[derive(Clone)]
 pub struct VertexData {
@@ -120,169 +83,1098 @@ impl Into<VertexData> for (Triple, Double, Triple, Triple) {
     }
 }
 
+impl Vertex for VertexData {
+    fn attributes() -> Vec<VertexAttribute> {
+        Self::ATTRIBUTES.to_vec()
+    }
+}
+
 impl VertexData {
-    fn make_vao(gl: Gl, vec: &Vec<Self>) -> GLuint {
-        let mut vao: gl::types::GLuint = 0;
-        unsafe {
-            gl.GenVertexArrays(1, &mut vao);
+    pub fn new(
+        coordinates: [f32; 3],
+        texture_coordinates: [f32; 2],
+        color: [f32; 3],
+        normal: [f32; 3],
+    ) -> Self {
+        Self {
+            coordinates: (coordinates[0], coordinates[1], coordinates[2]).into(),
+            texture_coordinates: (texture_coordinates[0], texture_coordinates[1]).into(),
+            color: (color[0], color[1], color[2]).into(),
+            normal: (normal[0], normal[1], normal[2]).into(),
         }
-        Triple::attach_to_vao(
-            gl.clone(),
-            &vec.iter().map(|vd| vd.coordinates.clone()).collect(),
-            vao,
-            0,
+    }
+
+    pub fn position(&self) -> Vector3<f32> {
+        let c = self.coordinates;
+        Vector3::new(c.x, c.y, c.z)
+    }
+
+    pub fn texture_coordinates(&self) -> (f32, f32) {
+        let t = self.texture_coordinates;
+        (t.x, t.y)
+    }
+
+    pub fn color(&self) -> Vector3<f32> {
+        let c = self.color;
+        Vector3::new(c.x, c.y, c.z)
+    }
+
+    pub fn normal(&self) -> Vector3<f32> {
+        let n = self.normal;
+        Vector3::new(n.x, n.y, n.z)
+    }
+
+    pub(super) fn set_normal(&mut self, normal: Vector3<f32>) {
+        self.normal = normal.into();
+    }
+
+    pub(super) fn set_texture_coordinates(&mut self, [u, v]: [f32; 2]) {
+        self.texture_coordinates = (u, v).into();
+    }
+
+    const ATTRIBUTES: [VertexAttribute; 4] = [
+        VertexAttribute::floats(0, 3, 0),
+        VertexAttribute::floats(1, 3, TRIPLE + DOUBLE),
+        VertexAttribute::floats(2, 2, TRIPLE),
+        VertexAttribute::floats(3, 3, TRIPLE + DOUBLE + TRIPLE),
+    ];
+
+    /// The bits of every component, so identical vertices compare and hash equal.
+    fn key(&self) -> [u32; 11] {
+        let (c, t, k, n) = (
+            self.coordinates,
+            self.texture_coordinates,
+            self.color,
+            self.normal,
         );
-        Triple::attach_to_vao(
-            gl.clone(),
-            &vec.iter().map(|vd| vd.color.clone()).collect(),
+        [c.x, c.y, c.z, t.x, t.y, k.x, k.y, k.z, n.x, n.y, n.z].map(f32::to_bits)
+    }
+}
+
+/// Indexed triangles kept on the CPU side.
+#[derive(Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<VertexData>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn aabb(&self) -> Aabb {
+        Aabb::from_points(self.vertices.iter().map(VertexData::position))
+    }
+
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        let points: Vec<Vector3<f32>> = self.vertices.iter().map(VertexData::position).collect();
+        BoundingSphere::from_points(&points)
+    }
+
+    /// Replaces all normals with the area weighted average of the triangles around each vertex.
+    pub fn smooth_normals(&mut self) {
+        let mut normals = vec![Vector3::zero(); self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let a = self.vertices[triangle[0] as usize].position();
+            let b = self.vertices[triangle[1] as usize].position();
+            let c = self.vertices[triangle[2] as usize].position();
+            let normal = (b - a).cross(c - a);
+            for index in triangle.iter() {
+                normals[*index as usize] += normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.set_normal(normalized(normal));
+        }
+    }
+}
+
+/// Element indices, stored in the narrowest type that fits both the indices and the vertex count,
+/// so later partial updates fit as well.
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    pub(super) fn new(indices: Vec<u32>, vertex_number: usize) -> Self {
+        let max = indices.iter().copied().max();
+        if let Some(max) = max {
+            assert!(
+                (max as usize) < vertex_number,
+                "Index {} is out of range for {} vertices",
+                max,
+                vertex_number
+            );
+        }
+        if max.unwrap_or(0) <= u16::MAX as u32 && vertex_number <= u16::MAX as usize + 1 {
+            Indices::U16(indices.into_iter().map(|i| i as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    fn gl_type(&self) -> GLenum {
+        match self {
+            Indices::U16(_) => gl::UNSIGNED_SHORT,
+            Indices::U32(_) => gl::UNSIGNED_INT,
+        }
+    }
+
+    fn index_size(&self) -> usize {
+        match self {
+            Indices::U16(_) => std::mem::size_of::<u16>(),
+            Indices::U32(_) => std::mem::size_of::<u32>(),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Indices::U16(indices) => std::mem::size_of_val(indices.as_slice()),
+            Indices::U32(indices) => std::mem::size_of_val(indices.as_slice()),
+        }
+    }
+
+    fn as_ptr(&self) -> *const GLvoid {
+        match self {
+            Indices::U16(indices) => indices.as_ptr() as *const GLvoid,
+            Indices::U32(indices) => indices.as_ptr() as *const GLvoid,
+        }
+    }
+
+    /// Returns the new element buffer.
+    fn attach_to_vao(&self, gl: Gl, vao: &VertexArray, usage: GLenum) -> Buffer {
+        let ebo = Buffer::new(gl.clone());
+        vao.bind();
+        ebo.bind(gl::ELEMENT_ARRAY_BUFFER);
+        unsafe {
+            gl.BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                self.size() as GLsizeiptr,
+                self.as_ptr(),
+                usage,
+            );
+
+            // The element buffer binding is part of the VAO state, so the VAO goes first.
+            gl.BindVertexArray(0);
+            gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+        ebo
+    }
+
+    /// Converts `indices` to the type of `self`, for writing over a part of it.
+    fn same_type(&self, indices: &[u32]) -> Self {
+        match self {
+            Indices::U16(_) => Indices::U16(
+                indices
+                    .iter()
+                    .map(|&i| {
+                        assert!(i <= u16::MAX as u32, "Index {} does not fit 16 bits", i);
+                        i as u16
+                    })
+                    .collect(),
+            ),
+            Indices::U32(_) => Indices::U32(indices.to_vec()),
+        }
+    }
+}
+
+/// Writes `size` bytes from the start of the buffer bound to `target`.
+/// A buffer that is big enough is orphaned first, so the driver does not have to wait
+/// for draws still reading the old contents; a smaller one is reallocated.
+unsafe fn stream(
+    gl: &Gl,
+    target: GLenum,
+    capacity: &mut usize,
+    size: usize,
+    data: *const GLvoid,
+    usage: GLenum,
+) {
+    if size > *capacity {
+        gl.BufferData(target, size as GLsizeiptr, data, usage);
+        *capacity = size;
+    } else {
+        gl.BufferData(target, *capacity as GLsizeiptr, std::ptr::null(), usage);
+        gl.BufferSubData(target, 0, size as GLsizeiptr, data);
+    }
+}
+
+/// Merges identical vertices of a triangle soup, returning the unique vertices and the indices into them.
+fn deduplicate(vertices: &[VertexData]) -> MeshData {
+    let mut unique: Vec<VertexData> = vec![];
+    let mut seen: HashMap<[u32; 11], u32> = HashMap::new();
+    let mut indices = Vec::with_capacity(vertices.len());
+    for vertex in vertices.iter() {
+        let index = *seen.entry(vertex.key()).or_insert_with(|| {
+            unique.push(vertex.clone());
+            (unique.len() - 1) as u32
+        });
+        indices.push(index);
+    }
+    MeshData {
+        vertices: unique,
+        indices,
+    }
+}
+
+pub struct Mesh {
+    gl: gl::Gl,
+    name: String,
+    vao: VertexArray,
+    vbo: Buffer,
+    ebo: Buffer,
+    /// `STATIC_DRAW` unless the mesh was made to be updated.
+    usage: GLenum,
+    vertex_size: usize,
+    vertex_number: usize,
+    vertex_capacity: usize,
+    index_capacity: usize,
+    render_mode: GLenum,
+    index_number: GLint,
+    index_type: GLenum,
+    material: Option<Rc<Material>>,
+    aabb: Aabb,
+    bounding_sphere: BoundingSphere,
+    /// Only kept when asked for, most meshes never need their geometry after the upload.
+    data: Option<MeshData>,
+}
+
+impl Mesh {
+    fn new(
+        gl: gl::Gl,
+        name: &str,
+        vertices: &[VertexData],
+        render_mode: GLenum,
+        keep_data: bool,
+    ) -> Self {
+        Self::from_data(gl, name, deduplicate(vertices), render_mode, keep_data)
+    }
+
+    pub(super) fn from_data(
+        gl: gl::Gl,
+        name: &str,
+        data: MeshData,
+        render_mode: GLenum,
+        keep_data: bool,
+    ) -> Self {
+        let indices = Indices::new(data.indices.clone(), data.vertices.len());
+        let mut mesh = Self::with_indices(gl, name, &data.vertices, indices, render_mode);
+        if keep_data {
+            mesh.data = Some(data);
+        }
+        mesh
+    }
+
+    pub(super) fn with_indices<V: Vertex>(
+        gl: gl::Gl,
+        name: &str,
+        vertices: &[V],
+        indices: Indices,
+        render_mode: GLenum,
+    ) -> Self {
+        Self::with_usage(gl, name, vertices, indices, render_mode, gl::STATIC_DRAW)
+    }
+
+    fn with_usage<V: Vertex>(
+        gl: gl::Gl,
+        name: &str,
+        vertices: &[V],
+        indices: Indices,
+        render_mode: GLenum,
+        usage: GLenum,
+    ) -> Self {
+        let (vao, vbo) = make_vao(gl.clone(), vertices, usage);
+        let ebo = indices.attach_to_vao(gl.clone(), &vao, usage);
+        let points = positions(vertices);
+
+        Self {
+            gl,
+            name: name.to_string(),
             vao,
-            1,
+            vbo,
+            ebo,
+            usage,
+            vertex_size: std::mem::size_of::<V>(),
+            vertex_number: vertices.len(),
+            vertex_capacity: std::mem::size_of_val(vertices),
+            index_capacity: indices.size(),
+            render_mode,
+            index_number: indices.len() as GLint,
+            index_type: indices.gl_type(),
+            material: None,
+            aabb: Aabb::from_points(points.iter().cloned()),
+            bounding_sphere: BoundingSphere::from_points(&points),
+            data: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn material(&self) -> Option<&Rc<Material>> {
+        self.material.as_ref()
+    }
+
+    pub(super) fn set_material(&mut self, material: Option<Rc<Material>>) {
+        self.material = material;
+    }
+
+    /// In model space, like the vertices.
+    pub fn aabb(&self) -> &Aabb {
+        &self.aabb
+    }
+
+    pub fn bounding_sphere(&self) -> &BoundingSphere {
+        &self.bounding_sphere
+    }
+
+    /// The geometry as it was uploaded, if the mesh was built to keep it.
+    pub fn data(&self) -> Option<&MeshData> {
+        self.data.as_ref()
+    }
+
+    /// Replaces all vertices. `V` has to be the vertex type the mesh was created with.
+    /// A kept copy of the geometry is dropped, as it no longer matches the buffers.
+    pub fn set_vertices<V: Vertex>(&mut self, vertices: &[V]) {
+        self.check_vertex_type::<V>();
+        unsafe {
+            self.vbo.bind(gl::ARRAY_BUFFER);
+            stream(
+                &self.gl,
+                gl::ARRAY_BUFFER,
+                &mut self.vertex_capacity,
+                std::mem::size_of_val(vertices),
+                vertices.as_ptr() as *const GLvoid,
+                self.usage,
+            );
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.vertex_number = vertices.len();
+        let points = positions(vertices);
+        self.aabb = Aabb::from_points(points.iter().cloned());
+        self.bounding_sphere = BoundingSphere::from_points(&points);
+        self.data = None;
+    }
+
+    /// Overwrites the vertices from `first` on, the vertex count stays the same.
+    /// The bounds only grow, so they stay correct but may get loose.
+    pub fn update_vertices<V: Vertex>(&mut self, first: usize, vertices: &[V]) {
+        self.check_vertex_type::<V>();
+        assert!(
+            first + vertices.len() <= self.vertex_number,
+            "Vertices {}..{} are out of range, the mesh has {}",
+            first,
+            first + vertices.len(),
+            self.vertex_number
         );
-        Double::attach_to_vao(
-            gl.clone(),
-            &vec.iter()
-                .map(|vd| vd.texture_coordinates.clone())
-                .collect(),
-            vao,
-            2,
+        unsafe {
+            self.vbo.bind(gl::ARRAY_BUFFER);
+            self.gl.BufferSubData(
+                gl::ARRAY_BUFFER,
+                (first * self.vertex_size) as GLintptr,
+                std::mem::size_of_val(vertices) as GLsizeiptr,
+                vertices.as_ptr() as *const GLvoid,
+            );
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.grow_bounds(&positions(vertices));
+        self.data = None;
+    }
+
+    /// Makes the bounds enclose `points` too.
+    pub(super) fn grow_bounds(&mut self, points: &[Vector3<f32>]) {
+        self.aabb = self.aabb.union(&Aabb::from_points(points.iter().cloned()));
+        self.bounding_sphere = self
+            .bounding_sphere
+            .enclose(self.aabb.center(), &BoundingSphere::from_points(points));
+    }
+
+    /// Replaces all indices, switching to 32 bit ones when the vertex count needs them.
+    pub fn set_indices(&mut self, indices: &[u32]) {
+        let indices = Indices::new(indices.to_vec(), self.vertex_number);
+        unsafe {
+            // The element buffer binding is part of the VAO state, so the VAO goes first.
+            self.vao.bind();
+            self.ebo.bind(gl::ELEMENT_ARRAY_BUFFER);
+            stream(
+                &self.gl,
+                gl::ELEMENT_ARRAY_BUFFER,
+                &mut self.index_capacity,
+                indices.size(),
+                indices.as_ptr(),
+                self.usage,
+            );
+            self.gl.BindVertexArray(0);
+        }
+        self.index_number = indices.len() as GLint;
+        self.index_type = indices.gl_type();
+        self.data = None;
+    }
+
+    /// Overwrites the indices from `first` on, the index count stays the same.
+    pub fn update_indices(&mut self, first: usize, indices: &[u32]) {
+        assert!(
+            first + indices.len() <= self.index_number as usize,
+            "Indices {}..{} are out of range, the mesh has {}",
+            first,
+            first + indices.len(),
+            self.index_number
         );
-        Triple::attach_to_vao(
-            gl.clone(),
-            &vec.iter().map(|vd| vd.normal.clone()).collect(),
-            vao,
-            3,
+        let current = match self.index_type {
+            gl::UNSIGNED_SHORT => Indices::U16(vec![]),
+            _ => Indices::U32(vec![]),
+        };
+        let indices = current.same_type(indices);
+        unsafe {
+            self.vao.bind();
+            self.ebo.bind(gl::ELEMENT_ARRAY_BUFFER);
+            self.gl.BufferSubData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (first * current.index_size()) as GLintptr,
+                indices.size() as GLsizeiptr,
+                indices.as_ptr(),
+            );
+            self.gl.BindVertexArray(0);
+        }
+        self.data = None;
+    }
+
+    fn check_vertex_type<V: Vertex>(&self) {
+        assert_eq!(
+            std::mem::size_of::<V>(),
+            self.vertex_size,
+            "The mesh was created with another vertex type"
         );
-        vao
+    }
+
+    pub fn draw(&self, gl: gl::Gl) {
+        unsafe {
+            gl.BindVertexArray(self.vao.id());
+            gl.DrawElements(
+                self.render_mode,
+                self.index_number,
+                self.index_type,
+                std::ptr::null(),
+            );
+            gl.BindVertexArray(0);
+        }
     }
 }
 
 pub struct Model {
-    gl: gl::Gl,
-    vao: GLuint,
-    render_mode: GLenum,
-    vertex_number: GLint,
+    meshes: Vec<Mesh>,
+    aabb: Aabb,
+    bounding_sphere: BoundingSphere,
 }
 
 impl Model {
+    pub(super) fn from_meshes(meshes: Vec<Mesh>) -> Self {
+        let mut model = Self {
+            meshes,
+            aabb: Aabb::empty(),
+            bounding_sphere: BoundingSphere {
+                center: Vector3::zero(),
+                radius: 0.0,
+            },
+        };
+        model.update_bounds();
+        model
+    }
+
+    fn update_bounds(&mut self) {
+        self.aabb = self
+            .meshes
+            .iter()
+            .fold(Aabb::empty(), |aabb, mesh| aabb.union(&mesh.aabb));
+        let center = if self.aabb.is_empty() {
+            Vector3::zero()
+        } else {
+            self.aabb.center()
+        };
+        self.bounding_sphere = self.meshes.iter().fold(
+            BoundingSphere {
+                center,
+                radius: 0.0,
+            },
+            |sphere, mesh| sphere.enclose(center, &mesh.bounding_sphere),
+        );
+    }
+
+    pub fn from_mesh_data(gl: gl::Gl, data: &MeshData) -> Self {
+        let indices = Indices::new(data.indices.clone(), data.vertices.len());
+        Self::from_meshes(vec![Mesh::with_indices(
+            gl,
+            DEFAULT_MESH_NAME,
+            &data.vertices,
+            indices,
+            gl::TRIANGLES,
+        )])
+    }
+
+    /// Like the generators and loaders, but the mesh keeps `data` for CPU side queries.
+    pub fn from_retained_mesh_data(gl: gl::Gl, data: MeshData) -> Self {
+        Self::from_meshes(vec![Mesh::from_data(
+            gl,
+            DEFAULT_MESH_NAME,
+            data,
+            gl::TRIANGLES,
+            true,
+        )])
+    }
+
+    /// Loads an ASCII or binary STL file.
+    pub fn from_stl(gl: gl::Gl, file_name: &Path) -> Result<Self, Error> {
+        let bytes = std::fs::read(file_name).map_err(|e| Error::io(file_name, e))?;
+        Ok(Self::from_mesh_data(gl, &parse_stl(file_name, &bytes)?))
+    }
+
+    /// Loads an ASCII or binary little endian PLY file, vertex colors go to the `color` attribute.
+    pub fn from_ply(gl: gl::Gl, file_name: &Path) -> Result<Self, Error> {
+        let bytes = std::fs::read(file_name).map_err(|e| Error::io(file_name, e))?;
+        Ok(Self::from_mesh_data(gl, &parse_ply(file_name, &bytes)?))
+    }
+
     pub fn cube(gl: gl::Gl) -> Self {
-        let vertices: Vec<VertexData> = cube_vertices();
+        Self::from_mesh_data(gl, &MeshData::cube())
+    }
 
-        let vao = VertexData::make_vao(gl.clone(), &vertices);
+    /// Builds a single mesh model out of any vertex type.
+    /// Without indices the vertices are drawn in the order they are given.
+    pub fn from_vertices<V: Vertex>(
+        gl: gl::Gl,
+        vertices: &[V],
+        indices: Option<&[u32]>,
+        render_mode: GLenum,
+    ) -> Self {
+        let indices = match indices {
+            Some(indices) => indices.to_vec(),
+            None => (0..vertices.len() as u32).collect(),
+        };
+        let indices = Indices::new(indices, vertices.len());
+        Self::from_meshes(vec![Mesh::with_indices(
+            gl,
+            DEFAULT_MESH_NAME,
+            vertices,
+            indices,
+            render_mode,
+        )])
+    }
 
-        Self {
-            gl: gl.clone(),
-            vao,
-            render_mode: gl::QUADS,
-            vertex_number: vertices.len() as i32,
+    /// Like `from_vertices`, but the buffers are meant to be changed often,
+    /// e.g. every frame with `set_vertices` or `update_vertices`.
+    pub fn dynamic<V: Vertex>(
+        gl: gl::Gl,
+        vertices: &[V],
+        indices: &[u32],
+        render_mode: GLenum,
+    ) -> Self {
+        let indices = Indices::new(indices.to_vec(), vertices.len());
+        Self::from_meshes(vec![Mesh::with_usage(
+            gl,
+            DEFAULT_MESH_NAME,
+            vertices,
+            indices,
+            render_mode,
+            gl::DYNAMIC_DRAW,
+        )])
+    }
+
+    /// Replaces the vertices of the `mesh`-th mesh, see `Mesh::set_vertices`.
+    pub fn set_vertices<V: Vertex>(&mut self, mesh: usize, vertices: &[V]) {
+        self.meshes[mesh].set_vertices(vertices);
+        self.update_bounds();
+    }
+
+    /// Overwrites a range of the vertices of the `mesh`-th mesh, see `Mesh::update_vertices`.
+    pub fn update_vertices<V: Vertex>(&mut self, mesh: usize, first: usize, vertices: &[V]) {
+        self.meshes[mesh].update_vertices(first, vertices);
+        self.update_bounds();
+    }
+
+    pub fn set_indices(&mut self, mesh: usize, indices: &[u32]) {
+        self.meshes[mesh].set_indices(indices);
+    }
+
+    pub fn update_indices(&mut self, mesh: usize, first: usize, indices: &[u32]) {
+        self.meshes[mesh].update_indices(first, indices);
+    }
+
+    /// Loads an OBJ file together with the `.mtl` libraries it references.
+    /// Faces are split into one mesh per `o`/`g` name and material.
+    pub fn from_obj(gl: gl::Gl, file_name: &Path) -> Result<Self, Error> {
+        Self::from_obj_with_options(gl, file_name, ObjOptions::default())
+    }
+
+    pub fn from_obj_with_options(
+        gl: gl::Gl,
+        file_name: &Path,
+        options: ObjOptions,
+    ) -> Result<Self, Error> {
+        let obj = read_obj(file_name, options)?;
+        let materials = obj.load_materials(gl.clone(), file_name)?;
+
+        let mut meshes = vec![];
+        for obj_mesh in obj.meshes.iter() {
+            let mut mesh = Mesh::new(
+                gl.clone(),
+                &obj_mesh.name,
+                &obj_mesh.vertices,
+                gl::TRIANGLES,
+                options.keep_mesh_data,
+            );
+            mesh.material = obj_mesh.find_material(&materials);
+            meshes.push(mesh);
         }
+
+        Ok(Self::from_meshes(meshes))
     }
 
-    pub fn from_obj(gl: gl::Gl, file_name: &std::path::Path) -> Result<Self, String> {
-        let file = match std::fs::read_to_string(file_name) {
-            Ok(f) => f,
-            Err(e) => format!("{}", e),
-        };
+    pub fn meshes(&self) -> &[Mesh] {
+        &self.meshes
+    }
 
-        let vertices = ObjParser::new().parse_obj(file.lines())?;
+    /// Encloses all meshes, in model space.
+    pub fn aabb(&self) -> &Aabb {
+        &self.aabb
+    }
 
-        let vao = VertexData::make_vao(gl.clone(), &vertices);
+    pub fn bounding_sphere(&self) -> &BoundingSphere {
+        &self.bounding_sphere
+    }
 
-        Ok(Self {
-            gl: gl.clone(),
-            vao,
-            render_mode: gl::TRIANGLES,
-            vertex_number: vertices.len() as i32,
-        })
+    /// A named part of the model may consist of several meshes, one per material.
+    pub fn meshes_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Mesh> {
+        self.meshes.iter().filter(move |m| m.name == name)
+    }
+
+    pub fn mesh_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = vec![];
+        for mesh in self.meshes.iter() {
+            if !names.contains(&mesh.name()) {
+                names.push(mesh.name());
+            }
+        }
+        names
     }
 
     pub fn draw(&self, gl: gl::Gl) {
-        unsafe {
-            gl.BindVertexArray(self.vao);
-            gl.DrawArrays(self.render_mode, 0, self.vertex_number);
-            gl.BindVertexArray(0);
+        for mesh in self.meshes.iter() {
+            mesh.draw(gl.clone());
+        }
+    }
+
+    pub fn draw_named(&self, gl: gl::Gl, name: &str) {
+        for mesh in self.meshes_named(name) {
+            mesh.draw(gl.clone());
         }
     }
 }
 
-impl Drop for Model {
-    fn drop(&mut self) {
-        unsafe {
-            self.gl.DeleteBuffers(1, &self.vao);
+/// Name of the meshes that come before any `o` or `g` statement.
+pub const DEFAULT_MESH_NAME: &str = "default";
+
+/// How normals are made up for OBJ faces that come without `vn` indices.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NormalGeneration {
+    /// Every face gets its own normal.
+    Flat,
+    /// The whole mesh is smoothed, smoothing groups are ignored.
+    Smooth,
+    /// Faces are smoothed within their `s` group, faces with `s off` stay flat.
+    SmoothingGroups,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ObjOptions {
+    pub normals: NormalGeneration,
+    /// Neighbouring faces meeting at a sharper angle than this are not smoothed together.
+    pub smoothing_angle: Rad<f32>,
+    /// Keeps the geometry of every mesh around, see `Mesh::data`.
+    pub keep_mesh_data: bool,
+}
+
+impl Default for ObjOptions {
+    fn default() -> Self {
+        Self {
+            normals: NormalGeneration::SmoothingGroups,
+            smoothing_angle: Rad(std::f32::consts::PI),
+            keep_mesh_data: false,
         }
     }
 }
 
+struct ObjFace {
+    vertices: Vec<VertexData>,
+    points: Vec<usize>,
+    smoothing_group: u32,
+    /// Per vertex, whether it came without a `vn` normal.
+    missing_normals: Vec<bool>,
+}
+
+pub(super) struct ObjMesh {
+    pub(super) name: String,
+    material: Option<String>,
+    faces: Vec<ObjFace>,
+    /// Three per triangle, before deduplication.
+    pub(super) vertices: Vec<VertexData>,
+}
+
+pub(super) struct ObjData {
+    material_libraries: Vec<String>,
+    pub(super) meshes: Vec<ObjMesh>,
+}
+
+impl ObjData {
+    /// Libraries are looked up next to `file_name`, the OBJ file itself.
+    pub(super) fn load_materials(
+        &self,
+        gl: gl::Gl,
+        file_name: &Path,
+    ) -> Result<Vec<Rc<Material>>, Error> {
+        let base = file_name.parent().unwrap_or_else(|| Path::new(""));
+        let mut materials = vec![];
+        for library in self.material_libraries.iter() {
+            for mut material in Material::from_mtl(&base.join(library))? {
+                material.load_textures(gl.clone())?;
+                materials.push(Rc::new(material));
+            }
+        }
+        Ok(materials)
+    }
+}
+
+impl ObjMesh {
+    pub(super) fn find_material(&self, materials: &[Rc<Material>]) -> Option<Rc<Material>> {
+        let name = self.material.as_ref()?;
+        materials.iter().find(|m| &m.name == name).cloned()
+    }
+}
+
+pub(super) fn read_obj(file_name: &Path, options: ObjOptions) -> Result<ObjData, Error> {
+    let file = std::fs::read_to_string(file_name).map_err(|e| Error::io(file_name, e))?;
+    ObjParser::with_options(options).parse_obj(file_name, file.lines())
+}
+
+/// Parses an OBJ file that is already in memory, for the tests of other modules.
+#[cfg(test)]
+pub(super) fn parse_obj_source(source: &str) -> Result<ObjData, Error> {
+    ObjParser::new().parse_obj(Path::new("test.obj"), source.lines())
+}
+
 struct ObjParser {
+    options: ObjOptions,
     points: Vec<Triple>,
     texture_coords: Vec<Double>,
     normals: Vec<Triple>,
-    vertices: Vec<VertexData>,
+    material_libraries: Vec<String>,
+    meshes: Vec<ObjMesh>,
+    current_mesh: Option<usize>,
+    current_object: Option<String>,
+    current_group: Option<String>,
+    current_material: Option<String>,
+    current_smoothing_group: u32,
 }
 
 impl ObjParser {
+    #[cfg(test)]
     fn new() -> Self {
+        Self::with_options(ObjOptions::default())
+    }
+
+    fn with_options(options: ObjOptions) -> Self {
         Self {
+            options,
             points: vec![],
             texture_coords: vec![],
             normals: vec![],
-            vertices: vec![],
+            material_libraries: vec![],
+            meshes: vec![],
+            current_mesh: None,
+            current_object: None,
+            current_group: None,
+            current_material: None,
+            current_smoothing_group: 0,
         }
     }
 
-    fn parse_obj<'a, I>(mut self, lines: I) -> Result<Vec<VertexData>, String>
+    fn parse_obj<'a, I>(mut self, file_name: &Path, lines: I) -> Result<ObjData, Error>
     where
         I: Iterator<Item = &'a str>,
     {
-        for line in lines {
-            if line.len() == 0 || line.chars().nth(0).unwrap() == '#' {
-                continue;
+        for (number, line) in lines.enumerate() {
+            self.parse_line(line)
+                .map_err(|message| Error::parse(file_name, number + 1, message))?;
+        }
+        self.meshes.retain(|m| !m.faces.is_empty());
+        generate_normals(&mut self.meshes, &self.options);
+        for mesh in self.meshes.iter_mut() {
+            for face in mesh.faces.iter() {
+                for i in 1..face.vertices.len() - 1 {
+                    mesh.vertices.push(face.vertices[0].clone());
+                    mesh.vertices.push(face.vertices[i].clone());
+                    mesh.vertices.push(face.vertices[i + 1].clone());
+                }
             }
-            let line = line.to_string();
-            let split: Vec<&str> = line.split(' ').filter(|s| s.len() != 0).collect();
-            if split.len() == 0 {
-                continue;
+        }
+        Ok(ObjData {
+            material_libraries: self.material_libraries,
+            meshes: self.meshes,
+        })
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let split: Vec<&str> = line.split_whitespace().collect();
+        if split.is_empty() || split[0].starts_with('#') {
+            return Ok(());
+        }
+        match split[0] {
+            "v" => self.points.push(parse_point_3(&split)?),
+            "vt" => self.texture_coords.push(parse_point_2(&split)?),
+            "vn" => self.normals.push(parse_point_3(&split)?),
+            "f" => self.parse_polygon(&split)?,
+            "mtllib" => self
+                .material_libraries
+                .extend(split[1..].iter().map(|s| s.to_string())),
+            "usemtl" if split.len() > 1 => {
+                self.current_material = Some(split[1].to_string());
+                self.current_mesh = None;
+            }
+            "o" => {
+                self.current_object = name_statement(&split);
+                self.current_group = None;
+                self.current_mesh = None;
             }
-            match split[0] {
-                "v" => self.points.push(parse_point_3(&split)?),
-                "vt" => self.texture_coords.push(parse_point_2(&split)?),
-                "vn" => self.normals.push(parse_point_3(&split)?),
-                "f" => self.parse_polygon(&split)?,
-                _ => continue,
+            "g" => {
+                self.current_group = name_statement(&split);
+                self.current_mesh = None;
             }
+            "s" if split.len() > 1 => {
+                self.current_smoothing_group = match split[1] {
+                    "off" => 0,
+                    group => parse::<u32>(group)?,
+                }
+            }
+            _ => {}
         }
-        Ok(self.vertices)
+        Ok(())
     }
 
-    fn parse_polygon(&mut self, split: &Vec<&str>) -> Result<(), String> {
-        if split.len() < 3 {
-            return Err("Not enough entities for a polygon".to_string());
+    /// `g` inside an `o` is named `object/group`; faces before any of them go to [`DEFAULT_MESH_NAME`].
+    fn current_name(&self) -> String {
+        match (&self.current_object, &self.current_group) {
+            (Some(object), Some(group)) => format!("{}/{}", object, group),
+            (Some(name), None) | (None, Some(name)) => name.clone(),
+            (None, None) => DEFAULT_MESH_NAME.to_string(),
         }
-        let base_vertex = self.parse_vertex_data(split[1])?;
+    }
+
+    /// Faces with the same name and material end up in the same mesh, wherever they are in the file.
+    fn current_faces(&mut self) -> &mut Vec<ObjFace> {
+        let index = match self.current_mesh {
+            Some(index) => index,
+            None => {
+                let name = self.current_name();
+                let material = &self.current_material;
+                let index = match self
+                    .meshes
+                    .iter()
+                    .position(|m| m.name == name && &m.material == material)
+                {
+                    Some(index) => index,
+                    None => {
+                        self.meshes.push(ObjMesh {
+                            name,
+                            material: material.clone(),
+                            faces: vec![],
+                            vertices: vec![],
+                        });
+                        self.meshes.len() - 1
+                    }
+                };
+                self.current_mesh = Some(index);
+                index
+            }
+        };
+        &mut self.meshes[index].faces
+    }
 
-        for i in 2..split.len() - 1 {
-            self.vertices.push(base_vertex.clone());
-            self.vertices.push(self.parse_vertex_data(split[i])?);
-            self.vertices.push(self.parse_vertex_data(split[i + 1])?);
+    fn parse_polygon(&mut self, split: &[&str]) -> Result<(), String> {
+        if split.len() < 4 {
+            return Err("Not enough entities for a polygon".to_string());
+        }
+        let mut face = ObjFace {
+            vertices: Vec::with_capacity(split.len() - 1),
+            points: Vec::with_capacity(split.len() - 1),
+            smoothing_group: match self.options.normals {
+                NormalGeneration::Flat => 0,
+                NormalGeneration::Smooth => 1,
+                NormalGeneration::SmoothingGroups => self.current_smoothing_group,
+            },
+            missing_normals: Vec::with_capacity(split.len() - 1),
+        };
+        for vertex in split[1..].iter() {
+            let (vertex, point, has_normal) = self.parse_vertex_data(vertex)?;
+            face.missing_normals.push(!has_normal);
+            face.vertices.push(vertex);
+            face.points.push(point);
         }
 
+        self.current_faces().push(face);
+
         Ok(())
     }
 
-    fn parse_vertex_data(&self, string: &str) -> Result<VertexData, String> {
+    /// Parses one face vertex in any of the `v`, `v/vt`, `v//vn` and `v/vt/vn` forms.
+    /// Also returns the index of its point and whether the vertex had its own normal.
+    fn parse_vertex_data(&self, string: &str) -> Result<(VertexData, usize, bool), String> {
         let split: Vec<&str> = string.split('/').collect();
-        let point = self.points[parse::<usize>(split[0])? - 1];
-        let texture = self.texture_coords[parse::<usize>(split[1])? - 1];
-        let normal = self.normals[parse::<usize>(split[2])? - 1];
-        Ok(VertexData {
-            color: (1.0, 1.0, 1.0).into(),
-            coordinates: point,
-            texture_coordinates: texture,
-            normal,
+        if split.len() > 3 {
+            return Err(format!("Too many indices in face vertex `{}`", string));
+        }
+        let point_index = resolve_index(split[0], self.points.len())?;
+        let point = self.points[point_index];
+        let texture = match split.get(1) {
+            Some(index) if !index.is_empty() => {
+                self.texture_coords[resolve_index(index, self.texture_coords.len())?]
+            }
+            _ => (0.0, 0.0).into(),
+        };
+        let normal = match split.get(2) {
+            Some(index) if !index.is_empty() => {
+                Some(self.normals[resolve_index(index, self.normals.len())?])
+            }
+            _ => None,
+        };
+        Ok((
+            VertexData {
+                color: (1.0, 1.0, 1.0).into(),
+                coordinates: point,
+                texture_coordinates: texture,
+                normal: normal.unwrap_or_else(|| (0.0, 0.0, 0.0).into()),
+            },
+            point_index,
+            normal.is_some(),
+        ))
+    }
+}
+
+fn name_statement(split: &[&str]) -> Option<String> {
+    if split.len() < 2 {
+        return None;
+    }
+    Some(split[1..].join(" "))
+}
+
+/// Turns a 1-based (or negative, relative to the end) OBJ index into a vector index.
+fn resolve_index(string: &str, len: usize) -> Result<usize, String> {
+    let index = parse::<i64>(string)?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        len as i64 + index
+    };
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!(
+            "Index {} is out of range, only {} elements are defined",
+            index, len
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+/// Fills in the normals of face vertices that have none. Corners of smoothed faces average the normals
+/// of all faces around the same point in the same smoothing group, weighted by their area.
+fn generate_normals(meshes: &mut [ObjMesh], options: &ObjOptions) {
+    let face_normals: Vec<Vec<Vector3<f32>>> = meshes
+        .iter()
+        .map(|mesh| {
+            mesh.faces
+                .iter()
+                .map(|f| polygon_normal(&f.vertices))
+                .collect()
         })
+        .collect();
+
+    let mut around_point: HashMap<(usize, u32), Vec<(usize, usize)>> = HashMap::new();
+    for (m, mesh) in meshes.iter().enumerate() {
+        for (f, face) in mesh.faces.iter().enumerate() {
+            if face.smoothing_group == 0 {
+                continue;
+            }
+            for point in face.points.iter() {
+                around_point
+                    .entry((*point, face.smoothing_group))
+                    .or_default()
+                    .push((m, f));
+            }
+        }
+    }
+
+    let min_cos = options.smoothing_angle.0.cos();
+    for (m, mesh) in meshes.iter_mut().enumerate() {
+        for (f, face) in mesh.faces.iter_mut().enumerate() {
+            if !face.missing_normals.contains(&true) {
+                continue;
+            }
+            let face_normal = face_normals[m][f];
+            let vertices = face.vertices.iter_mut().zip(face.points.iter());
+            for ((vertex, point), _) in vertices
+                .zip(face.missing_normals.iter())
+                .filter(|(_, &missing)| missing)
+            {
+                let mut normal = face_normal;
+                if face.smoothing_group != 0 {
+                    normal = Vector3::zero();
+                    for (other_m, other_f) in around_point[&(*point, face.smoothing_group)].iter() {
+                        let other_normal = face_normals[*other_m][*other_f];
+                        if (*other_m, *other_f) == (m, f)
+                            || cos_between(face_normal, other_normal) >= min_cos
+                        {
+                            normal += other_normal;
+                        }
+                    }
+                }
+                vertex.normal = normalized(normal).into();
+            }
+        }
     }
 }
 
-fn parse_point_3(split: &Vec<&str>) -> Result<Triple, String> {
+fn cos_between(a: Vector3<f32>, b: Vector3<f32>) -> f32 {
+    let len = a.magnitude() * b.magnitude();
+    if len == 0.0 {
+        return 1.0;
+    }
+    a.dot(b) / len
+}
+
+fn normalized(vector: Vector3<f32>) -> Vector3<f32> {
+    if vector.magnitude2() == 0.0 {
+        return vector;
+    }
+    vector.normalize()
+}
+
+/// Newell's method, so that concave and slightly non-planar polygons still get a sane normal.
+/// The length of the result is twice the area of the polygon.
+fn polygon_normal(polygon: &[VertexData]) -> Vector3<f32> {
+    let mut normal = Vector3::zero();
+    for i in 0..polygon.len() {
+        let a = polygon[i].coordinates;
+        let b = polygon[(i + 1) % polygon.len()].coordinates;
+        normal.x += (a.y - b.y) * (a.z + b.z);
+        normal.y += (a.z - b.z) * (a.x + b.x);
+        normal.z += (a.x - b.x) * (a.y + b.y);
+    }
+    normal
+}
+
+fn parse_point_3(split: &[&str]) -> Result<Triple, String> {
     if split.len() < 4 {
         return Err("Not enough coordinates for a point 3".to_string());
     }
@@ -295,7 +1187,7 @@ fn parse_point_3(split: &Vec<&str>) -> Result<Triple, String> {
     Ok((coords[0], coords[1], coords[2]).into())
 }
 
-fn parse_point_2(split: &Vec<&str>) -> Result<Double, String> {
+fn parse_point_2(split: &[&str]) -> Result<Double, String> {
     if split.len() < 3 {
         return Err("Not enough coordinates for a point 3".to_string());
     }
@@ -308,64 +1200,250 @@ fn parse_point_2(split: &Vec<&str>) -> Result<Double, String> {
     Ok((coords[0], 1.0 - coords[1]).into())
 }
 
-fn parse<T>(string: &str) -> Result<T, String>
-where
-    T: std::str::FromStr,
-{
-    match string.parse::<T>() {
-        Ok(f) => Ok(f),
-        Err(_) => {
-            return Err(format!(
-                "Could not parse `{}` into {}",
-                string,
-                std::any::type_name::<T>()
-            ))
-        }
-    }
-}
-
-#[rustfmt::skip]
-fn cube_vertices() -> Vec<VertexData> {
-    let t0_3: f32 = 1.0 / 3.0;
-    let t0_6: f32 = 2.0 / 3.0;
-    let p000: Triple = (-0.5, -0.5, -0.5).into();
-    let p001: Triple = (-0.5, -0.5,  0.5).into();
-    let p010: Triple = (-0.5,  0.5, -0.5).into();
-    let p011: Triple = (-0.5,  0.5,  0.5).into();
-    let p100: Triple = ( 0.5, -0.5, -0.5).into();
-    let p101: Triple = ( 0.5, -0.5,  0.5).into();
-    let p110: Triple = ( 0.5,  0.5, -0.5).into();
-    let p111: Triple = ( 0.5,  0.5,  0.5).into();
-    vec![
-        // BOTTOM
-        (p000, (t0_3, 0.25).into(), (0.0, 0.0, 0.0).into(), (0.0, -1.0, 0.0).into()).into(),
-        (p001, (t0_3, 0.50).into(), (0.0, 0.0, 1.0).into(), (0.0, -1.0, 0.0).into()).into(),
-        (p101, (t0_6, 0.50).into(), (1.0, 0.0, 1.0).into(), (0.0, -1.0, 0.0).into()).into(),
-        (p100, (t0_6, 0.25).into(), (1.0, 0.0, 0.0).into(), (0.0, -1.0, 0.0).into()).into(),
-        // FRONT
-        (p001, (t0_3, 0.50).into(), (0.0, 0.0, 1.0).into(), (0.0, 0.0, 1.0).into()).into(),
-        (p011, (t0_3, 0.75).into(), (0.0, 1.0, 1.0).into(), (0.0, 0.0, 1.0).into()).into(),
-        (p111, (t0_6, 0.75).into(), (1.0, 1.0, 1.0).into(), (0.0, 0.0, 1.0).into()).into(),
-        (p101, (t0_6, 0.50).into(), (1.0, 0.0, 1.0).into(), (0.0, 0.0, 1.0).into()).into(),
-        // TOP
-        (p011, (t0_3, 0.75).into(), (0.0, 1.0, 1.0).into(), (0.0, 1.0, 0.0).into()).into(),
-        (p010, (t0_3, 1.00).into(), (0.0, 1.0, 0.0).into(), (0.0, 1.0, 0.0).into()).into(),
-        (p110, (t0_6, 1.00).into(), (1.0, 1.0, 0.0).into(), (0.0, 1.0, 0.0).into()).into(),
-        (p111, (t0_6, 0.75).into(), (1.0, 1.0, 1.0).into(), (0.0, 1.0, 0.0).into()).into(),
-        // BACK
-        (p000, (t0_3, 0.25).into(), (0.0, 0.0, 0.0).into(), (0.0, 0.0, -1.0).into()).into(),
-        (p010, (t0_3, 0.00).into(), (0.0, 1.0, 0.0).into(), (0.0, 0.0, -1.0).into()).into(),
-        (p110, (t0_6, 0.00).into(), (1.0, 1.0, 0.0).into(), (0.0, 0.0, -1.0).into()).into(),
-        (p100, (t0_6, 0.25).into(), (1.0, 0.0, 0.0).into(), (0.0, 0.0, -1.0).into()).into(),
-        // LEFT
-        (p000, (t0_3, 0.25).into(), (0.0, 0.0, 0.0).into(), (-1.0, 0.0, 0.0).into()).into(),
-        (p010, (0.00, 0.25).into(), (0.0, 1.0, 0.0).into(), (-1.0, 0.0, 0.0).into()).into(),
-        (p011, (0.00, 0.50).into(), (0.0, 1.0, 1.0).into(), (-1.0, 0.0, 0.0).into()).into(),
-        (p001, (t0_3, 0.50).into(), (0.0, 0.0, 1.0).into(), (-1.0, 0.0, 0.0).into()).into(),
-        // RIGHT
-        (p110, (1.00, 0.25).into(), (1.0, 1.0, 0.0).into(), (1.0, 0.0, 0.0).into()).into(),
-        (p111, (1.00, 0.50).into(), (1.0, 1.0, 1.0).into(), (1.0, 0.0, 0.0).into()).into(),
-        (p101, (t0_6, 0.50).into(), (1.0, 0.0, 1.0).into(), (1.0, 0.0, 0.0).into()).into(),
-        (p100, (t0_6, 0.25).into(), (1.0, 0.0, 0.0).into(), (1.0, 0.0, 0.0).into()).into(),
-    ]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+";
+
+    fn parse(faces: &str) -> Result<Vec<VertexData>, Error> {
+        let source = format!("{}{}", SQUARE, faces);
+        let obj = ObjParser::new().parse_obj(Path::new("test.obj"), source.lines())?;
+        Ok(obj.meshes.into_iter().flat_map(|m| m.vertices).collect())
+    }
+
+    fn coordinates(vertex: &VertexData) -> (f32, f32, f32) {
+        let c = vertex.coordinates;
+        (c.x, c.y, c.z)
+    }
+
+    fn texture_coordinates(vertex: &VertexData) -> (f32, f32) {
+        let t = vertex.texture_coordinates;
+        (t.x, t.y)
+    }
+
+    fn normal(vertex: &VertexData) -> (f32, f32, f32) {
+        let n = vertex.normal;
+        (n.x, n.y, n.z)
+    }
+
+    #[test]
+    fn full_form() {
+        let vertices = parse("f 1/1/1 2/2/1 3/3/1").unwrap();
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(coordinates(&vertices[1]), (1.0, 0.0, 0.0));
+        assert_eq!(texture_coordinates(&vertices[1]), (1.0, 1.0));
+        assert_eq!(normal(&vertices[1]), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn position_only() {
+        let vertices = parse("f 1 2 3 4").unwrap();
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(coordinates(&vertices[5]), (0.0, 1.0, 0.0));
+        assert_eq!(texture_coordinates(&vertices[5]), (0.0, 0.0));
+        assert_eq!(normal(&vertices[5]), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn position_and_texture() {
+        let vertices = parse("f 1/1 2/2 3/3").unwrap();
+        assert_eq!(texture_coordinates(&vertices[2]), (1.0, 0.0));
+        assert_eq!(normal(&vertices[2]), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn position_and_normal() {
+        let vertices = parse("vn 0 0 -1\nf 3//2 2//2 1//2").unwrap();
+        assert_eq!(coordinates(&vertices[0]), (1.0, 1.0, 0.0));
+        assert_eq!(texture_coordinates(&vertices[0]), (0.0, 0.0));
+        assert_eq!(normal(&vertices[0]), (0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn partial_normals() {
+        let vertices = parse("vn 1 0 0\nf 1//2 2 3").unwrap();
+        assert_eq!(normal(&vertices[0]), (1.0, 0.0, 0.0));
+        assert_eq!(normal(&vertices[1]), (0.0, 0.0, 1.0));
+        assert_eq!(normal(&vertices[2]), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn negative_indices() {
+        let vertices = parse("f -4/-4/-1 -3/-3/-1 -2/-2/-1").unwrap();
+        assert_eq!(coordinates(&vertices[0]), (0.0, 0.0, 0.0));
+        assert_eq!(coordinates(&vertices[2]), (1.0, 1.0, 0.0));
+        assert_eq!(texture_coordinates(&vertices[2]), (1.0, 0.0));
+    }
+
+    #[test]
+    fn error_location() {
+        match parse("f 1 2 3\nf 1 2 5") {
+            Err(Error::Parse { path, line, .. }) => {
+                assert_eq!(path, Path::new("test.obj"));
+                assert_eq!(line, 12);
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn out_of_range_indices() {
+        assert!(parse("f 1 2 5").is_err());
+        assert!(parse("f 1 2 -5").is_err());
+        assert!(parse("f 0 1 2").is_err());
+        assert!(parse("f 1/5 2/1 3/1").is_err());
+        assert!(parse("f 1//2 2//1 3//1").is_err());
+    }
+
+    #[test]
+    fn meshes_per_material() {
+        let source = format!(
+            "{}mtllib a.mtl b.mtl\nf 1 2 3\nusemtl red\nf 1 2 3\nusemtl blue\nf 1 3 4\nusemtl red\nf 2 3 4",
+            SQUARE
+        );
+        let obj = ObjParser::new()
+            .parse_obj(Path::new("test.obj"), source.lines())
+            .unwrap();
+        assert_eq!(obj.material_libraries, vec!["a.mtl", "b.mtl"]);
+        let meshes: Vec<(Option<&str>, usize)> = obj
+            .meshes
+            .iter()
+            .map(|m| (m.material.as_deref(), m.vertices.len()))
+            .collect();
+        assert_eq!(meshes, vec![(None, 3), (Some("red"), 6), (Some("blue"), 3)]);
+    }
+
+    #[test]
+    fn meshes_per_name() {
+        let source = format!(
+            "{}f 1 2 3\no torch\ng head\nf 1 2 3\nusemtl red\nf 1 2 3\ng handle\nf 1 3 4\no stand\nf 2 3 4\ng head\nf 1 2 4",
+            SQUARE
+        );
+        let obj = ObjParser::new()
+            .parse_obj(Path::new("test.obj"), source.lines())
+            .unwrap();
+        let meshes: Vec<(&str, Option<&str>, usize)> = obj
+            .meshes
+            .iter()
+            .map(|m| (m.name.as_str(), m.material.as_deref(), m.vertices.len()))
+            .collect();
+        assert_eq!(
+            meshes,
+            vec![
+                (DEFAULT_MESH_NAME, None, 3),
+                ("torch/head", None, 3),
+                ("torch/head", Some("red"), 3),
+                ("torch/handle", Some("red"), 3),
+                ("stand", Some("red"), 3),
+                ("stand/head", Some("red"), 3),
+            ]
+        );
+    }
+
+    const CORNER: &str = "v 0 0 1\nv 1 0 1\nf 1 2 3 4\nf 1 5 6 2";
+
+    fn parse_corner(prefix: &str, options: ObjOptions) -> Vec<VertexData> {
+        let source = format!("{}{}{}", SQUARE, prefix, CORNER);
+        let obj = ObjParser::with_options(options)
+            .parse_obj(Path::new("test.obj"), source.lines())
+            .unwrap();
+        obj.meshes.into_iter().flat_map(|m| m.vertices).collect()
+    }
+
+    fn assert_normal(vertex: &VertexData, expected: (f32, f32, f32)) {
+        let (x, y, z) = normal(vertex);
+        let error = (x - expected.0).abs() + (y - expected.1).abs() + (z - expected.2).abs();
+        assert!(error < 1e-5, "{:?} != {:?}", (x, y, z), expected);
+    }
+
+    #[test]
+    fn smoothing_groups() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let vertices = parse_corner("s 1\n", ObjOptions::default());
+        // Point 1 is shared by both faces, point 3 only by the first one.
+        assert_normal(&vertices[0], (0.0, half, half));
+        assert_normal(&vertices[2], (0.0, 0.0, 1.0));
+        assert_normal(&vertices[6], (0.0, half, half));
+
+        let vertices = parse_corner("s off\n", ObjOptions::default());
+        assert_normal(&vertices[0], (0.0, 0.0, 1.0));
+        assert_normal(&vertices[6], (0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn normal_generation_modes() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let smooth = ObjOptions {
+            normals: NormalGeneration::Smooth,
+            ..ObjOptions::default()
+        };
+        assert_normal(&parse_corner("", smooth)[0], (0.0, half, half));
+
+        let sharp = ObjOptions {
+            smoothing_angle: Rad(1.0),
+            ..smooth
+        };
+        assert_normal(&parse_corner("", sharp)[0], (0.0, 0.0, 1.0));
+
+        let flat = ObjOptions {
+            normals: NormalGeneration::Flat,
+            ..ObjOptions::default()
+        };
+        assert_normal(&parse_corner("s 1\n", flat)[0], (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn deduplicates_torch() {
+        let source = std::fs::read_to_string("src/resources/torch.obj").unwrap();
+        let obj = ObjParser::new()
+            .parse_obj(Path::new("test.obj"), source.lines())
+            .unwrap();
+        let expanded = &obj.meshes[0].vertices;
+        let data = deduplicate(expanded);
+        let unique = &data.vertices;
+
+        assert_eq!(expanded.len(), 36);
+        assert_eq!(unique.len(), 24);
+        let indices = match Indices::new(data.indices.clone(), unique.len()) {
+            Indices::U16(indices) => indices,
+            Indices::U32(_) => panic!("16 bits are enough for the torch"),
+        };
+        assert_eq!(indices.len(), expanded.len());
+        for (vertex, index) in expanded.iter().zip(indices.iter()) {
+            assert_eq!(vertex.key(), unique[*index as usize].key());
+        }
+    }
+
+    #[test]
+    fn wide_indices() {
+        let narrow = Indices::new(vec![0, 65535], 65536);
+        assert_eq!(narrow.gl_type(), gl::UNSIGNED_SHORT);
+        let wide = Indices::new(vec![0, 65536], 65537);
+        assert_eq!(wide.gl_type(), gl::UNSIGNED_INT);
+        assert_eq!(wide.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 65536 is out of range for 4 vertices")]
+    fn out_of_range_element_index() {
+        Indices::new(vec![0, 1, 65536], 4);
+    }
+
+    #[test]
+    fn malformed_faces() {
+        assert!(parse("f 1 2").is_err());
+        assert!(parse("f 1/1/1/1 2 3").is_err());
+        assert!(parse("f a 2 3").is_err());
+    }
 }
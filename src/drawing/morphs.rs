@@ -0,0 +1,351 @@
+use cgmath::{Vector4, VectorSpace};
+use std::{collections::HashMap, path::Path, rc::Rc};
+
+use super::{
+    animations::{sample_keyframes, Interpolation},
+    models::{read_obj, Indices, ObjData, VertexData},
+    vertex_format, Error, Mesh, Model, ObjOptions,
+};
+
+/// The size of the `morph_weights` uniform and of the delta arrays in the vertex shaders.
+pub const MAX_MORPH_TARGETS: usize = 4;
+
+vertex_format! {
+    /// A vertex with the offsets that move it into each blend shape.
+    /// Locations match `VertexData` where the attributes overlap.
+    pub struct MorphVertex {
+        0 => pub position: [f32; 3],
+        1 => pub color: [f32; 3],
+        2 => pub texture_coordinates: [f32; 2],
+        3 => pub normal: [f32; 3],
+        7 => pub position_delta_0: [f32; 3],
+        8 => pub position_delta_1: [f32; 3],
+        9 => pub position_delta_2: [f32; 3],
+        10 => pub position_delta_3: [f32; 3],
+        11 => pub normal_delta_0: [f32; 3],
+        12 => pub normal_delta_1: [f32; 3],
+        13 => pub normal_delta_2: [f32; 3],
+        14 => pub normal_delta_3: [f32; 3],
+    }
+}
+
+impl MorphVertex {
+    /// Missing targets get no offsets.
+    fn new(base: &VertexData, targets: &[&VertexData]) -> Self {
+        let mut positions = [[0.0; 3]; MAX_MORPH_TARGETS];
+        let mut normals = [[0.0; 3]; MAX_MORPH_TARGETS];
+        for (i, target) in targets.iter().enumerate() {
+            positions[i] = (target.position() - base.position()).into();
+            normals[i] = (target.normal() - base.normal()).into();
+        }
+        let (u, v) = base.texture_coordinates();
+        Self {
+            position: base.position().into(),
+            color: base.color().into(),
+            texture_coordinates: [u, v],
+            normal: base.normal().into(),
+            position_delta_0: positions[0],
+            position_delta_1: positions[1],
+            position_delta_2: positions[2],
+            position_delta_3: positions[3],
+            normal_delta_0: normals[0],
+            normal_delta_1: normals[1],
+            normal_delta_2: normals[2],
+            normal_delta_3: normals[3],
+        }
+    }
+
+    fn key(&self) -> Vec<u32> {
+        [
+            self.position,
+            self.color,
+            self.normal,
+            self.position_delta_0,
+            self.position_delta_1,
+            self.position_delta_2,
+            self.position_delta_3,
+            self.normal_delta_0,
+            self.normal_delta_1,
+            self.normal_delta_2,
+            self.normal_delta_3,
+        ]
+        .iter()
+        .flatten()
+        .chain(self.texture_coordinates.iter())
+        .map(|f| f.to_bits())
+        .collect()
+    }
+}
+
+/// Indexed vertices out of the unindexed triangles of the base shape and of its targets.
+/// Corners are only shared where they match in every shape.
+fn morph_vertices(base: &[VertexData], targets: &[&[VertexData]]) -> (Vec<MorphVertex>, Vec<u32>) {
+    let mut unique = vec![];
+    let mut seen: HashMap<Vec<u32>, u32> = HashMap::new();
+    let mut indices = Vec::with_capacity(base.len());
+    for (i, vertex) in base.iter().enumerate() {
+        let shapes: Vec<&VertexData> = targets.iter().map(|target| &target[i]).collect();
+        let vertex = MorphVertex::new(vertex, &shapes);
+        let index = *seen.entry(vertex.key()).or_insert_with(|| {
+            unique.push(vertex);
+            (unique.len() - 1) as u32
+        });
+        indices.push(index);
+    }
+    (unique, indices)
+}
+
+fn check_topology(base: &ObjData, target: &ObjData, path: &Path) -> Result<(), Error> {
+    if base.meshes.len() != target.meshes.len() {
+        return Err(Error::invalid_data(
+            path,
+            format!(
+                "{} meshes where the base shape has {}",
+                target.meshes.len(),
+                base.meshes.len()
+            ),
+        ));
+    }
+    for (base, target) in base.meshes.iter().zip(target.meshes.iter()) {
+        if base.vertices.len() != target.vertices.len() {
+            return Err(Error::invalid_data(
+                path,
+                format!(
+                    "mesh `{}` has {} triangles where the base shape has {}",
+                    target.name,
+                    target.vertices.len() / 3,
+                    base.vertices.len() / 3
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl Model {
+    /// Loads `base` with up to `MAX_MORPH_TARGETS` blend shapes, to be drawn with `morph_weights`.
+    /// Each target has to be the same OBJ with its vertices moved: the same meshes, faces and
+    /// face order. The bounds cover the base and every target at full weight.
+    pub fn from_obj_with_morph_targets(
+        gl: gl::Gl,
+        base: &Path,
+        targets: &[&Path],
+    ) -> Result<Self, Error> {
+        if targets.len() > MAX_MORPH_TARGETS {
+            return Err(Error::invalid_data(
+                base,
+                format!(
+                    "{} morph targets given, at most {} are supported",
+                    targets.len(),
+                    MAX_MORPH_TARGETS
+                ),
+            ));
+        }
+        let obj = read_obj(base, ObjOptions::default())?;
+        let materials = obj.load_materials(gl.clone(), base)?;
+        let mut shapes = vec![];
+        for path in targets.iter() {
+            let target = read_obj(path, ObjOptions::default())?;
+            check_topology(&obj, &target, path)?;
+            shapes.push(target);
+        }
+
+        let mut meshes = vec![];
+        for (i, obj_mesh) in obj.meshes.iter().enumerate() {
+            let targets: Vec<&[VertexData]> = shapes
+                .iter()
+                .map(|shape| shape.meshes[i].vertices.as_slice())
+                .collect();
+            let (vertices, indices) = morph_vertices(&obj_mesh.vertices, &targets);
+            let vertex_number = vertices.len();
+            let mut mesh = Mesh::with_indices(
+                gl.clone(),
+                &obj_mesh.name,
+                &vertices,
+                Indices::new(indices, vertex_number),
+                gl::TRIANGLES,
+            );
+            for target in targets.iter() {
+                let points: Vec<_> = target.iter().map(VertexData::position).collect();
+                mesh.grow_bounds(&points);
+            }
+            mesh.set_material(obj_mesh.find_material(&materials));
+            meshes.push(mesh);
+        }
+        Ok(Self::from_meshes(meshes))
+    }
+}
+
+/// Blend shape weights over time, one component per target.
+pub struct MorphAnimation {
+    times: Vec<f32>,
+    weights: Vec<Vector4<f32>>,
+    interpolation: Interpolation,
+}
+
+impl MorphAnimation {
+    /// `weights` has a value per time, or three with `Interpolation::CubicSpline`, see `Channel`.
+    pub fn new(times: Vec<f32>, weights: Vec<Vector4<f32>>, interpolation: Interpolation) -> Self {
+        let per_key = match interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+        assert!(!times.is_empty(), "There has to be at least one keyframe");
+        assert_eq!(
+            weights.len(),
+            times.len() * per_key,
+            "Keyframe times and weights do not match"
+        );
+        Self {
+            times,
+            weights,
+            interpolation,
+        }
+    }
+
+    /// Weights that never change.
+    pub fn fixed(weights: Vector4<f32>) -> Self {
+        Self::new(vec![0.0], vec![weights], Interpolation::Step)
+    }
+
+    /// The time of the last keyframe, in seconds.
+    pub fn duration(&self) -> f32 {
+        *self.times.last().unwrap()
+    }
+
+    pub fn sample(&self, time: f32) -> Vector4<f32> {
+        sample_keyframes(
+            &self.times,
+            self.interpolation,
+            &self.weights,
+            time,
+            |a, b, t| a.lerp(b, t),
+        )
+    }
+}
+
+/// Plays a `MorphAnimation`, several entities may share one.
+pub struct MorphAnimator {
+    animation: Rc<MorphAnimation>,
+    time: f32,
+    pub speed: f32,
+    pub looping: bool,
+}
+
+impl MorphAnimator {
+    pub fn new(animation: Rc<MorphAnimation>) -> Self {
+        Self {
+            animation,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+
+    pub fn advance(&mut self, secs: f32) {
+        self.time += secs * self.speed;
+    }
+
+    /// The value for the `morph_weights` uniform.
+    pub fn weights(&self) -> Vector4<f32> {
+        let duration = self.animation.duration();
+        let time = if self.looping && duration > 0.0 {
+            self.time.rem_euclid(duration)
+        } else {
+            self.time
+        };
+        self.animation.sample(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drawing::models::{parse_obj_source, DEFAULT_MESH_NAME};
+
+    fn vertex(x: f32, normal: [f32; 3]) -> VertexData {
+        VertexData::new([x, 0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0], normal)
+    }
+
+    #[test]
+    fn blend_shape_vertices() {
+        let up = [0.0, 1.0, 0.0];
+        let base = [vertex(0.0, up), vertex(1.0, up), vertex(0.0, up)];
+        // The first and the last corner match in the base but not in the target.
+        let target = [
+            vertex(0.0, up),
+            vertex(3.0, [1.0, 0.0, 0.0]),
+            vertex(5.0, up),
+        ];
+
+        let (vertices, indices) = morph_vertices(&base, &[&target]);
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert_eq!(vertices[1].position, [1.0, 0.0, 0.0]);
+        assert_eq!(vertices[1].position_delta_0, [2.0, 0.0, 0.0]);
+        assert_eq!(vertices[1].normal_delta_0, [1.0, -1.0, 0.0]);
+        assert_eq!(vertices[1].position_delta_1, [0.0, 0.0, 0.0]);
+
+        let (vertices, indices) = morph_vertices(&base, &[&base]);
+        assert_eq!(vertices.len(), 2);
+        assert_eq!(indices, vec![0, 1, 0]);
+
+        // Texture coordinates are part of the key as well.
+        let mut seam = base.clone();
+        seam[2] = VertexData::new([0.0, 0.0, 0.0], [0.5, 0.0], [1.0, 1.0, 1.0], up);
+        let (vertices, indices) = morph_vertices(&seam, &[&seam]);
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn topology_mismatch() {
+        let points = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+        let obj = |faces: &str| parse_obj_source(&format!("{}{}", points, faces)).unwrap();
+        let base = obj("f 1 2 3 4");
+        let path = Path::new("target.obj");
+
+        assert!(check_topology(&base, &obj("f 2 3 4 1"), path).is_ok());
+        assert_eq!(
+            check_topology(&base, &obj("f 1 2 3"), path)
+                .err()
+                .unwrap()
+                .to_string(),
+            format!(
+                "target.obj: mesh `{}` has 1 triangles where the base shape has 2",
+                DEFAULT_MESH_NAME
+            )
+        );
+        assert_eq!(
+            check_topology(&base, &obj("f 1 2 3\no lid\nf 1 3 4"), path)
+                .err()
+                .unwrap()
+                .to_string(),
+            "target.obj: 2 meshes where the base shape has 1"
+        );
+    }
+
+    #[test]
+    fn weights_over_time() {
+        let animation = Rc::new(MorphAnimation::new(
+            vec![0.0, 1.0, 2.0],
+            vec![
+                Vector4::new(0.0, 0.0, 0.0, 0.0),
+                Vector4::new(1.0, 0.0, 0.0, 0.0),
+                Vector4::new(0.0, 1.0, 0.0, 0.0),
+            ],
+            Interpolation::Linear,
+        ));
+        assert_eq!(animation.duration(), 2.0);
+        assert_eq!(animation.sample(1.5), Vector4::new(0.5, 0.5, 0.0, 0.0));
+
+        let mut animator = MorphAnimator::new(animation.clone());
+        animator.advance(2.5);
+        assert_eq!(animator.weights(), Vector4::new(0.5, 0.0, 0.0, 0.0));
+        animator.looping = false;
+        assert_eq!(animator.weights(), Vector4::new(0.0, 1.0, 0.0, 0.0));
+
+        let fixed = MorphAnimation::fixed(Vector4::new(0.25, 0.0, 0.0, 0.0));
+        assert_eq!(fixed.sample(10.0), Vector4::new(0.25, 0.0, 0.0, 0.0));
+    }
+}
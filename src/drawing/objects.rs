@@ -0,0 +1,220 @@
+use gl::{
+    types::{GLenum, GLsizei, GLuint},
+    Gl,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The kinds of GL objects that are counted in debug builds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectKind {
+    Buffer,
+    VertexArray,
+    Framebuffer,
+    Renderbuffer,
+    Texture,
+    Sampler,
+}
+
+const KINDS: [ObjectKind; 6] = [
+    ObjectKind::Buffer,
+    ObjectKind::VertexArray,
+    ObjectKind::Framebuffer,
+    ObjectKind::Renderbuffer,
+    ObjectKind::Texture,
+    ObjectKind::Sampler,
+];
+
+static LIVE: [AtomicUsize; 6] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+pub(super) fn created(kind: ObjectKind) {
+    if cfg!(debug_assertions) {
+        LIVE[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(super) fn deleted(kind: ObjectKind) {
+    if cfg!(debug_assertions) {
+        LIVE[kind as usize].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// How many objects of every kind exist right now. Always zeros in release builds.
+pub fn live_objects() -> Vec<(ObjectKind, usize)> {
+    KINDS
+        .iter()
+        .map(|&kind| (kind, LIVE[kind as usize].load(Ordering::Relaxed)))
+        .collect()
+}
+
+fn leak_report(live: &[(ObjectKind, usize)]) -> Option<String> {
+    let leaked: Vec<String> = live
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(kind, count)| format!("{} {:?}", count, kind))
+        .collect();
+    if leaked.is_empty() {
+        None
+    } else {
+        Some(format!(
+            "GL objects were not deleted: {}",
+            leaked.join(", ")
+        ))
+    }
+}
+
+/// Prints the objects that are still alive. Meant for shutdown, once everything
+/// holding GL objects has been dropped and while the context is still current.
+pub fn report_leaks() {
+    if let Some(report) = leak_report(&live_objects()) {
+        eprintln!("{}", report);
+    }
+}
+
+/// Declares an owned GL object that is generated on creation and deleted on drop.
+macro_rules! gl_object {
+    (
+        $(#[$meta:meta])*
+        $name:ident, $kind:ident, $generate:ident, $delete:ident
+    ) => {
+        $(#[$meta])*
+        pub struct $name {
+            gl: Gl,
+            id: GLuint,
+        }
+
+        impl $name {
+            pub fn new(gl: Gl) -> Self {
+                let mut id = 0;
+                unsafe {
+                    gl.$generate(1, &mut id);
+                }
+                created(ObjectKind::$kind);
+                Self { gl, id }
+            }
+
+            pub fn id(&self) -> GLuint {
+                self.id
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                unsafe {
+                    self.gl.$delete(1, &self.id);
+                }
+                deleted(ObjectKind::$kind);
+            }
+        }
+    };
+}
+
+gl_object! {
+    /// Bound to whatever target it is used for, see `Buffer::bind`.
+    Buffer, Buffer, GenBuffers, DeleteBuffers
+}
+gl_object! { VertexArray, VertexArray, GenVertexArrays, DeleteVertexArrays }
+gl_object! { Framebuffer, Framebuffer, GenFramebuffers, DeleteFramebuffers }
+gl_object! { Renderbuffer, Renderbuffer, GenRenderbuffers, DeleteRenderbuffers }
+
+impl Buffer {
+    pub fn bind(&self, target: GLenum) {
+        unsafe {
+            self.gl.BindBuffer(target, self.id);
+        }
+    }
+}
+
+impl VertexArray {
+    pub fn bind(&self) {
+        unsafe {
+            self.gl.BindVertexArray(self.id);
+        }
+    }
+}
+
+impl Framebuffer {
+    /// `target` is `FRAMEBUFFER`, `READ_FRAMEBUFFER` or `DRAW_FRAMEBUFFER`.
+    pub fn bind(&self, target: GLenum) {
+        unsafe {
+            self.gl.BindFramebuffer(target, self.id);
+        }
+    }
+
+    /// Attaches level 0 of a 2D texture at `attachment`, e.g. `COLOR_ATTACHMENT0`.
+    /// Leaves the framebuffer bound to `FRAMEBUFFER`, like the other attachment methods.
+    pub fn attach_texture(&self, attachment: GLenum, texture: GLuint) {
+        self.bind(gl::FRAMEBUFFER);
+        unsafe {
+            self.gl
+                .FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, texture, 0);
+        }
+    }
+
+    pub fn attach_renderbuffer(&self, attachment: GLenum, renderbuffer: &Renderbuffer) {
+        self.bind(gl::FRAMEBUFFER);
+        unsafe {
+            self.gl.FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                attachment,
+                gl::RENDERBUFFER,
+                renderbuffer.id,
+            );
+        }
+    }
+
+    /// `FRAMEBUFFER_COMPLETE` once the attachments can be drawn to.
+    pub fn status(&self) -> GLenum {
+        self.bind(gl::FRAMEBUFFER);
+        unsafe { self.gl.CheckFramebufferStatus(gl::FRAMEBUFFER) }
+    }
+}
+
+impl Renderbuffer {
+    pub fn bind(&self) {
+        unsafe {
+            self.gl.BindRenderbuffer(gl::RENDERBUFFER, self.id);
+        }
+    }
+
+    /// Allocates `width` x `height` pixels of a sized `internal_format`, e.g. `DEPTH24_STENCIL8`.
+    pub fn allocate(&self, internal_format: GLenum, width: u32, height: u32) {
+        self.bind();
+        unsafe {
+            self.gl.RenderbufferStorage(
+                gl::RENDERBUFFER,
+                internal_format,
+                width as GLsizei,
+                height as GLsizei,
+            );
+            self.gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_only_leaked_kinds() {
+        let clean = [(ObjectKind::Buffer, 0), (ObjectKind::Texture, 0)];
+        assert_eq!(leak_report(&clean), None);
+
+        let leaked = [
+            (ObjectKind::Buffer, 2),
+            (ObjectKind::VertexArray, 0),
+            (ObjectKind::Texture, 1),
+        ];
+        assert_eq!(
+            leak_report(&leaked).unwrap(),
+            "GL objects were not deleted: 2 Buffer, 1 Texture"
+        );
+    }
+}
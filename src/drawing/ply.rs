@@ -0,0 +1,399 @@
+use std::{convert::TryInto, path::Path};
+
+use super::{
+    models::{MeshData, VertexData},
+    parse, Error,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Result<Self, String> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(format!("Unknown property type `{}`", name)),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// Integer colors are stored in the whole range of their type, this maps them to `0.0..=1.0`.
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::U8 => 255.0,
+            Scalar::U16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    kind: Scalar,
+    /// The type of the length prefix for list properties.
+    list: Option<Scalar>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reads a PLY file. Faces are triangulated as fans, missing normals are smoothed over the faces.
+pub(super) fn parse_ply(file_name: &Path, bytes: &[u8]) -> Result<MeshData, Error> {
+    let header_end = match find(bytes, b"end_header") {
+        Some(position) => position,
+        None => return Err(Error::invalid_data(file_name, "No PLY header".to_string())),
+    };
+    let body_start = match bytes[header_end..].iter().position(|b| *b == b'\n') {
+        Some(position) => header_end + position + 1,
+        None => bytes.len(),
+    };
+    let header = String::from_utf8_lossy(&bytes[..header_end]);
+    let (format, elements) =
+        parse_header(&header).map_err(|(line, message)| Error::parse(file_name, line, message))?;
+
+    let body = &bytes[body_start..];
+    let header_lines = header.lines().count() + 1;
+    let ascii_body;
+    let mut reader = match format {
+        Format::Ascii => {
+            ascii_body = String::from_utf8_lossy(body);
+            Reader::Ascii {
+                tokens: ascii_body
+                    .lines()
+                    .enumerate()
+                    .flat_map(|(number, line)| {
+                        line.split_whitespace()
+                            .map(move |token| (header_lines + number + 1, token))
+                    })
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+                line: header_lines,
+            }
+        }
+        Format::BinaryLittleEndian => Reader::Binary {
+            bytes: body,
+            offset: 0,
+        },
+    };
+
+    read_body(&mut reader, &elements).map_err(|message| match reader {
+        Reader::Ascii { line, .. } => Error::parse(file_name, line, message),
+        Reader::Binary { offset, .. } => Error::invalid_data(
+            file_name,
+            format!("{} at byte {}", message, body_start + offset),
+        ),
+    })
+}
+
+fn find(bytes: &[u8], pattern: &[u8]) -> Option<usize> {
+    bytes.windows(pattern.len()).position(|w| w == pattern)
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), (usize, String)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for (number, line) in header.lines().enumerate() {
+        let error = |message: String| (number + 1, message);
+        let split: Vec<&str> = line.split_whitespace().collect();
+        match split.as_slice() {
+            ["ply"] | [] => {}
+            ["comment", ..] | ["obj_info", ..] => {}
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", other, ..] => return Err(error(format!("Unsupported format `{}`", other))),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: parse::<usize>(count).map_err(error)?,
+                properties: vec![],
+            }),
+            ["property", "list", count, kind, name] => {
+                let property = Property {
+                    name: name.to_string(),
+                    kind: Scalar::from_name(kind).map_err(error)?,
+                    list: Some(Scalar::from_name(count).map_err(error)?),
+                };
+                match elements.last_mut() {
+                    Some(element) => element.properties.push(property),
+                    None => return Err(error("Property outside of an element".to_string())),
+                }
+            }
+            ["property", kind, name] => {
+                let property = Property {
+                    name: name.to_string(),
+                    kind: Scalar::from_name(kind).map_err(error)?,
+                    list: None,
+                };
+                match elements.last_mut() {
+                    Some(element) => element.properties.push(property),
+                    None => return Err(error("Property outside of an element".to_string())),
+                }
+            }
+            _ => return Err(error(format!("Unexpected header line `{}`", line))),
+        }
+    }
+    match format {
+        Some(format) => Ok((format, elements)),
+        None => Err((1, "No format line in the header".to_string())),
+    }
+}
+
+enum Reader<'a> {
+    /// Tokens together with their line numbers, `line` is the one of the last read token.
+    Ascii {
+        tokens: std::vec::IntoIter<(usize, &'a str)>,
+        line: usize,
+    },
+    Binary {
+        bytes: &'a [u8],
+        offset: usize,
+    },
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, kind: Scalar) -> Result<f64, String> {
+        match self {
+            Reader::Ascii { tokens, line } => match tokens.next() {
+                Some((number, token)) => {
+                    *line = number;
+                    parse::<f64>(token)
+                }
+                None => Err("Unexpected end of file".to_string()),
+            },
+            Reader::Binary { bytes, offset } => {
+                let size = kind.size();
+                if *offset + size > bytes.len() {
+                    return Err("Unexpected end of file".to_string());
+                }
+                let b = &bytes[*offset..*offset + size];
+                *offset += size;
+                Ok(match kind {
+                    Scalar::I8 => b[0] as i8 as f64,
+                    Scalar::U8 => b[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes(b.try_into().unwrap()) as f64,
+                    Scalar::U16 => u16::from_le_bytes(b.try_into().unwrap()) as f64,
+                    Scalar::I32 => i32::from_le_bytes(b.try_into().unwrap()) as f64,
+                    Scalar::U32 => u32::from_le_bytes(b.try_into().unwrap()) as f64,
+                    Scalar::F32 => f32::from_le_bytes(b.try_into().unwrap()) as f64,
+                    Scalar::F64 => f64::from_le_bytes(b.try_into().unwrap()),
+                })
+            }
+        }
+    }
+}
+
+fn read_body(reader: &mut Reader, elements: &[Element]) -> Result<MeshData, String> {
+    let mut data = MeshData::default();
+    let mut has_normals = false;
+    for element in elements.iter() {
+        let position = |name: &str| element.properties.iter().position(|p| p.name == name);
+        let find_any = |names: &[&str]| names.iter().find_map(|name| position(name));
+        let xyz = [position("x"), position("y"), position("z")];
+        let normal = [position("nx"), position("ny"), position("nz")];
+        let uv = [
+            find_any(&["u", "s", "texture_u", "texture_s"]),
+            find_any(&["v", "t", "texture_v", "texture_t"]),
+        ];
+        let rgb = [position("red"), position("green"), position("blue")];
+        let face = find_any(&["vertex_indices", "vertex_index"]);
+        has_normals |= element.name == "vertex" && normal.iter().all(|n| n.is_some());
+
+        for _ in 0..element.count {
+            let mut values = Vec::with_capacity(element.properties.len());
+            let mut list = vec![];
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.list {
+                    Some(count_kind) => {
+                        // Not preallocated, a corrupt count runs out of data instead.
+                        let count = reader.read(count_kind)? as usize;
+                        let mut items = vec![];
+                        for _ in 0..count {
+                            items.push(reader.read(property.kind)?);
+                        }
+                        if Some(i) == face {
+                            list = items;
+                        }
+                        values.push(0.0);
+                    }
+                    None => values.push(reader.read(property.kind)?),
+                }
+            }
+
+            let get = |index: Option<usize>, default: f64| index.map_or(default, |i| values[i]);
+            match element.name.as_str() {
+                "vertex" => {
+                    let scale = |i: Option<usize>| {
+                        i.map_or(1.0, |i| element.properties[i].kind.color_scale())
+                    };
+                    data.vertices.push(VertexData::new(
+                        [
+                            get(xyz[0], 0.0) as f32,
+                            get(xyz[1], 0.0) as f32,
+                            get(xyz[2], 0.0) as f32,
+                        ],
+                        // Flipped the same way as OBJ texture coordinates.
+                        [get(uv[0], 0.0) as f32, 1.0 - get(uv[1], 0.0) as f32],
+                        [
+                            (get(rgb[0], 1.0) / scale(rgb[0])) as f32,
+                            (get(rgb[1], 1.0) / scale(rgb[1])) as f32,
+                            (get(rgb[2], 1.0) / scale(rgb[2])) as f32,
+                        ],
+                        [
+                            get(normal[0], 0.0) as f32,
+                            get(normal[1], 0.0) as f32,
+                            get(normal[2], 0.0) as f32,
+                        ],
+                    ));
+                }
+                "face" => {
+                    let invalid = |i: &&f64| **i < 0.0 || i.fract() != 0.0 || **i > u32::MAX as f64;
+                    if let Some(index) = list.iter().find(invalid) {
+                        return Err(format!("Face index {} is not a vertex number", index));
+                    }
+                    for i in 1..list.len().saturating_sub(1) {
+                        data.indices.push(list[0] as u32);
+                        data.indices.push(list[i] as u32);
+                        data.indices.push(list[i + 1] as u32);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(index) = data
+        .indices
+        .iter()
+        .find(|i| **i as usize >= data.vertices.len())
+    {
+        return Err(format!(
+            "Index {} is out of range, only {} vertices are defined",
+            index,
+            data.vertices.len()
+        ));
+    }
+    if !has_normals {
+        data.smooth_normals();
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    const HEADER: &str = "ply
+format {} 1.0
+comment made by hand
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    #[test]
+    fn ascii() {
+        let source = HEADER.replace("{}", "ascii")
+            + "0 0 0 255 0 0\n1 0 0 0 255 0\n1 1 0 0 0 255\n0 1 0 255 255 255\n4 0 1 2 3\n";
+        let data = parse_ply(Path::new("test.ply"), source.as_bytes()).unwrap();
+        assert_eq!(data.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(data.vertices[1].color(), Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(data.vertices[2].position(), Vector3::new(1.0, 1.0, 0.0));
+        assert_eq!(data.vertices[3].normal(), Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn binary_little_endian() {
+        let mut bytes = HEADER.replace("{}", "binary_little_endian").into_bytes();
+        let points = [
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        for point in points.iter() {
+            for value in point.iter() {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&[0, 0, 255]);
+        }
+        bytes.push(3);
+        for index in [0i32, 1, 2].iter() {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let data = parse_ply(Path::new("test.ply"), &bytes).unwrap();
+        assert_eq!(data.indices, vec![0, 1, 2]);
+        assert_eq!(data.vertices[3].position(), Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(data.vertices[0].color(), Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn errors() {
+        let truncated = HEADER.replace("{}", "ascii") + "0 0 0 255 0 0\n";
+        match parse_ply(Path::new("test.ply"), truncated.as_bytes()) {
+            Err(Error::Parse { .. }) => {}
+            _ => panic!("expected a parse error"),
+        }
+
+        let out_of_range = HEADER.replace("{}", "ascii")
+            + "0 0 0 0 0 0\n0 0 0 0 0 0\n0 0 0 0 0 0\n0 0 0 0 0 0\n3 0 1 7\n";
+        assert!(parse_ply(Path::new("test.ply"), out_of_range.as_bytes()).is_err());
+
+        for face in ["3 0 1 -1", "3 0 1.5 2"].iter() {
+            let source = HEADER.replace("{}", "ascii")
+                + "0 0 0 0 0 0\n0 0 0 0 0 0\n0 0 0 0 0 0\n0 0 0 0 0 0\n"
+                + face;
+            match parse_ply(Path::new("test.ply"), source.as_bytes()) {
+                Err(Error::Parse { message, .. }) => assert!(message.starts_with("Face index")),
+                _ => panic!("expected a parse error for `{}`", face),
+            }
+        }
+
+        let huge_list = HEADER.replace("{}", "ascii")
+            + "0 0 0 0 0 0\n0 0 0 0 0 0\n0 0 0 0 0 0\n0 0 0 0 0 0\n1e30 0 1 2\n";
+        assert!(parse_ply(Path::new("test.ply"), huge_list.as_bytes()).is_err());
+
+        let big_endian = HEADER.replace("{}", "binary_big_endian");
+        assert!(parse_ply(Path::new("test.ply"), big_endian.as_bytes()).is_err());
+    }
+}
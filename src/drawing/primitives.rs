@@ -0,0 +1,458 @@
+use cgmath::{InnerSpace, Vector3};
+use std::{collections::HashMap, f32::consts::PI};
+
+use super::{
+    models::{MeshData, VertexData},
+    MeshBuilder, Model,
+};
+
+/// Generated shapes are centered at the origin, with `y` up and counterclockwise front faces.
+/// Texture coordinates follow the loaders: `v` grows downwards from the top of the image.
+impl MeshData {
+    /// A unit cube with the texture laid out as an unfolded cross and every corner colored
+    /// by its position.
+    pub fn cube() -> Self {
+        let (t0_3, t0_6) = (1.0 / 3.0, 2.0 / 3.0);
+        // Corners of every face with their texture coordinates, counterclockwise from outside.
+        #[rustfmt::skip]
+        let faces = [
+            // BOTTOM
+            ([0.0, -1.0, 0.0], [
+                ([-0.5, -0.5, -0.5], [t0_3, 0.25]), ([0.5, -0.5, -0.5], [t0_6, 0.25]),
+                ([0.5, -0.5, 0.5], [t0_6, 0.50]), ([-0.5, -0.5, 0.5], [t0_3, 0.50]),
+            ]),
+            // FRONT
+            ([0.0, 0.0, 1.0], [
+                ([-0.5, -0.5, 0.5], [t0_3, 0.50]), ([0.5, -0.5, 0.5], [t0_6, 0.50]),
+                ([0.5, 0.5, 0.5], [t0_6, 0.75]), ([-0.5, 0.5, 0.5], [t0_3, 0.75]),
+            ]),
+            // TOP
+            ([0.0, 1.0, 0.0], [
+                ([-0.5, 0.5, 0.5], [t0_3, 0.75]), ([0.5, 0.5, 0.5], [t0_6, 0.75]),
+                ([0.5, 0.5, -0.5], [t0_6, 1.00]), ([-0.5, 0.5, -0.5], [t0_3, 1.00]),
+            ]),
+            // BACK
+            ([0.0, 0.0, -1.0], [
+                ([-0.5, -0.5, -0.5], [t0_3, 0.25]), ([-0.5, 0.5, -0.5], [t0_3, 0.00]),
+                ([0.5, 0.5, -0.5], [t0_6, 0.00]), ([0.5, -0.5, -0.5], [t0_6, 0.25]),
+            ]),
+            // LEFT
+            ([-1.0, 0.0, 0.0], [
+                ([-0.5, -0.5, -0.5], [t0_3, 0.25]), ([-0.5, -0.5, 0.5], [t0_3, 0.50]),
+                ([-0.5, 0.5, 0.5], [0.00, 0.50]), ([-0.5, 0.5, -0.5], [0.00, 0.25]),
+            ]),
+            // RIGHT
+            ([1.0, 0.0, 0.0], [
+                ([0.5, -0.5, -0.5], [t0_6, 0.25]), ([0.5, 0.5, -0.5], [1.00, 0.25]),
+                ([0.5, 0.5, 0.5], [1.00, 0.50]), ([0.5, -0.5, 0.5], [t0_6, 0.50]),
+            ]),
+        ];
+
+        let mut builder = MeshBuilder::new();
+        for (normal, corners) in faces.iter() {
+            let [a, b, c, d] = corners.map(|(position, texture_coordinates)| {
+                let color = position.map(|p| p + 0.5);
+                builder.add_vertex(VertexData::new(
+                    position,
+                    texture_coordinates,
+                    color,
+                    *normal,
+                ))
+            });
+            builder.add_quad(a, b, c, d);
+        }
+        builder.build()
+    }
+
+    /// `sectors` go around the `y` axis, `stacks` from the north pole to the south one.
+    pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Self {
+        let (sectors, stacks) = (sectors.max(3), stacks.max(2));
+        let mut builder = MeshBuilder::new();
+        for row in 0..=stacks {
+            let theta = PI * row as f32 / stacks as f32;
+            for column in 0..=sectors {
+                let normal =
+                    around_y(column, sectors) * theta.sin() + Vector3::unit_y() * theta.cos();
+                builder.add_point(
+                    normal * radius,
+                    [column as f32 / sectors as f32, row as f32 / stacks as f32],
+                    normal,
+                );
+            }
+        }
+        connect_grid(&mut builder, stacks, sectors);
+        builder.build()
+    }
+
+    /// An icosahedron with every triangle split in four `subdivisions` times.
+    /// Triangles crossing the texture seam get their own vertices.
+    pub fn icosphere(radius: f32, subdivisions: u32) -> Self {
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut points: Vec<Vector3<f32>> = [
+            (-1.0, t, 0.0),
+            (1.0, t, 0.0),
+            (-1.0, -t, 0.0),
+            (1.0, -t, 0.0),
+            (0.0, -1.0, t),
+            (0.0, 1.0, t),
+            (0.0, -1.0, -t),
+            (0.0, 1.0, -t),
+            (t, 0.0, -1.0),
+            (t, 0.0, 1.0),
+            (-t, 0.0, -1.0),
+            (-t, 0.0, 1.0),
+        ]
+        .iter()
+        .map(|&(x, y, z)| Vector3::new(x, y, z).normalize())
+        .collect();
+        #[rustfmt::skip]
+        let mut triangles: Vec<[usize; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            let mut middles: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut middle = |a: usize, b: usize, points: &mut Vec<Vector3<f32>>| {
+                *middles.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    points.push((points[a] + points[b]).normalize());
+                    points.len() - 1
+                })
+            };
+            let mut split = Vec::with_capacity(triangles.len() * 4);
+            for &[a, b, c] in triangles.iter() {
+                let ab = middle(a, b, &mut points);
+                let bc = middle(b, c, &mut points);
+                let ca = middle(c, a, &mut points);
+                split.push([a, ab, ca]);
+                split.push([b, bc, ab]);
+                split.push([c, ca, bc]);
+                split.push([ab, bc, ca]);
+            }
+            triangles = split;
+        }
+
+        let mut builder = MeshBuilder::new();
+        let mut emitted: HashMap<(usize, u32), u32> = HashMap::new();
+        for triangle in triangles.iter() {
+            let mut u = triangle.map(|i| longitude(points[i]));
+            let poles = triangle.map(|i| points[i].y.abs() > 1.0 - 1e-6);
+            // Pole vertices have no longitude of their own, they take the one of the other corners.
+            for i in 0..3 {
+                if poles[i] {
+                    u[i] = (u[(i + 1) % 3] + u[(i + 2) % 3]) / 2.0;
+                }
+            }
+            let max = u.iter().cloned().fold(f32::MIN, f32::max);
+            let min = u.iter().cloned().fold(f32::MAX, f32::min);
+            if max - min > 0.5 {
+                for value in u.iter_mut() {
+                    if *value < 0.5 {
+                        *value += 1.0;
+                    }
+                }
+                if poles.iter().any(|p| *p) {
+                    let pole = poles.iter().position(|p| *p).unwrap();
+                    u[pole] = (u[(pole + 1) % 3] + u[(pole + 2) % 3]) / 2.0;
+                }
+            }
+            let mut corners = [0; 3];
+            for ((&point, &u), corner) in triangle.iter().zip(u.iter()).zip(corners.iter_mut()) {
+                let normal = points[point];
+                *corner = *emitted.entry((point, u.to_bits())).or_insert_with(|| {
+                    let v = normal.y.acos() / PI;
+                    builder.add_point(normal * radius, [u, v], normal)
+                });
+            }
+            builder.add_polygon(&corners);
+        }
+        builder.build()
+    }
+
+    /// A flat grid in the `xz` plane facing `+y`.
+    pub fn plane(width: f32, depth: f32, x_segments: u32, z_segments: u32) -> Self {
+        let (x_segments, z_segments) = (x_segments.max(1), z_segments.max(1));
+        let mut builder = MeshBuilder::new();
+        for row in 0..=z_segments {
+            let v = row as f32 / z_segments as f32;
+            for column in 0..=x_segments {
+                let u = column as f32 / x_segments as f32;
+                builder.add_point(
+                    Vector3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth),
+                    [u, v],
+                    Vector3::unit_y(),
+                );
+            }
+        }
+        connect_grid(&mut builder, z_segments, x_segments);
+        builder.build()
+    }
+
+    /// A closed cylinder along the `y` axis.
+    pub fn cylinder(radius: f32, height: f32, sectors: u32) -> Self {
+        Self::frustum(radius, radius, height, sectors)
+    }
+
+    /// A closed cone along the `y` axis with the apex at the top.
+    pub fn cone(radius: f32, height: f32, sectors: u32) -> Self {
+        Self::frustum(0.0, radius, height, sectors)
+    }
+
+    /// A ring around the `y` axis, `major_radius` is measured to the middle of the tube.
+    pub fn torus(
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> Self {
+        let (major_segments, minor_segments) = (major_segments.max(3), minor_segments.max(3));
+        let mut builder = MeshBuilder::new();
+        for row in 0..=minor_segments {
+            // Starts at the top of the tube and goes down its outer side first.
+            let theta = PI / 2.0 - 2.0 * PI * row as f32 / minor_segments as f32;
+            for column in 0..=major_segments {
+                let outwards = around_y(column, major_segments);
+                let normal = outwards * theta.cos() + Vector3::unit_y() * theta.sin();
+                builder.add_point(
+                    outwards * major_radius + normal * minor_radius,
+                    [
+                        column as f32 / major_segments as f32,
+                        row as f32 / minor_segments as f32,
+                    ],
+                    normal,
+                );
+            }
+        }
+        connect_grid(&mut builder, minor_segments, major_segments);
+        builder.build()
+    }
+
+    /// A cylinder of `height` capped with hemispheres, so the whole shape is `height + 2 * radius` tall.
+    /// `stacks` are per hemisphere.
+    pub fn capsule(radius: f32, height: f32, sectors: u32, stacks: u32) -> Self {
+        let (sectors, stacks) = (sectors.max(3), stacks.max(1));
+        // `v` follows the length of the profile, so the texture does not stretch on the cylinder.
+        let length = PI * radius + height;
+        let mut builder = MeshBuilder::new();
+        for (offset, first) in [(height / 2.0, 0.0), (-height / 2.0, PI / 2.0)].iter() {
+            for row in 0..=stacks {
+                let theta = first + PI / 2.0 * row as f32 / stacks as f32;
+                let v = if *offset > 0.0 {
+                    theta * radius / length
+                } else {
+                    (theta * radius + height) / length
+                };
+                for column in 0..=sectors {
+                    let normal =
+                        around_y(column, sectors) * theta.sin() + Vector3::unit_y() * theta.cos();
+                    builder.add_point(
+                        normal * radius + Vector3::unit_y() * *offset,
+                        [column as f32 / sectors as f32, v],
+                        normal,
+                    );
+                }
+            }
+        }
+        connect_grid(&mut builder, 2 * stacks + 1, sectors);
+        builder.build()
+    }
+
+    /// Covers cylinders and cones, caps are only added where the radius is not zero.
+    fn frustum(top_radius: f32, bottom_radius: f32, height: f32, sectors: u32) -> Self {
+        let sectors = sectors.max(3);
+        let mut builder = MeshBuilder::new();
+        for (row, (radius, y)) in [(top_radius, height / 2.0), (bottom_radius, -height / 2.0)]
+            .iter()
+            .enumerate()
+        {
+            for column in 0..=sectors {
+                let outwards = around_y(column, sectors);
+                let normal = (outwards * height + Vector3::unit_y() * (bottom_radius - top_radius))
+                    .normalize();
+                builder.add_point(
+                    outwards * *radius + Vector3::unit_y() * *y,
+                    [column as f32 / sectors as f32, row as f32],
+                    normal,
+                );
+            }
+        }
+        connect_grid(&mut builder, 1, sectors);
+
+        for (radius, up) in [(top_radius, 1.0), (bottom_radius, -1.0)].iter() {
+            if *radius == 0.0 {
+                continue;
+            }
+            let normal = Vector3::unit_y() * *up;
+            let center = builder.add_point(normal * (height / 2.0), [0.5, 0.5], normal);
+            for column in 0..=sectors {
+                let outwards = around_y(column, sectors);
+                builder.add_point(
+                    outwards * *radius + normal * (height / 2.0),
+                    [0.5 + outwards.x / 2.0, 0.5 + outwards.z * *up / 2.0],
+                    normal,
+                );
+            }
+            for column in 0..sectors {
+                let (a, b) = (center + 1 + column, center + 2 + column);
+                if *up > 0.0 {
+                    builder.add_triangle(center, a, b);
+                } else {
+                    builder.add_triangle(center, b, a);
+                }
+            }
+        }
+        builder.build()
+    }
+}
+
+/// Connects `rows + 1` lines of `columns + 1` vertices, the first line being the first vertices of `builder`.
+/// Lines have to go from the top to the bottom and vertices from left to right, as seen from the front.
+fn connect_grid(builder: &mut MeshBuilder, rows: u32, columns: u32) {
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * (columns + 1) + column;
+            let b = a + columns + 1;
+            builder.add_quad(a, b, b + 1, a + 1);
+        }
+    }
+}
+
+/// The horizontal direction of `column` out of `columns` around the `y` axis.
+/// It turns counterclockwise as seen from above, starting from `+x`.
+fn around_y(column: u32, columns: u32) -> Vector3<f32> {
+    let phi = 2.0 * PI * column as f32 / columns as f32;
+    Vector3::new(phi.cos(), 0.0, -phi.sin())
+}
+
+/// The inverse of `around_y`, from 0 to 1.
+fn longitude(point: Vector3<f32>) -> f32 {
+    let phi = (-point.z).atan2(point.x);
+    if phi < 0.0 {
+        phi / (2.0 * PI) + 1.0
+    } else {
+        phi / (2.0 * PI)
+    }
+}
+
+impl Model {
+    pub fn uv_sphere(gl: gl::Gl, radius: f32, sectors: u32, stacks: u32) -> Self {
+        Self::from_mesh_data(gl, &MeshData::uv_sphere(radius, sectors, stacks))
+    }
+
+    pub fn icosphere(gl: gl::Gl, radius: f32, subdivisions: u32) -> Self {
+        Self::from_mesh_data(gl, &MeshData::icosphere(radius, subdivisions))
+    }
+
+    pub fn plane(gl: gl::Gl, width: f32, depth: f32, x_segments: u32, z_segments: u32) -> Self {
+        Self::from_mesh_data(gl, &MeshData::plane(width, depth, x_segments, z_segments))
+    }
+
+    pub fn cylinder(gl: gl::Gl, radius: f32, height: f32, sectors: u32) -> Self {
+        Self::from_mesh_data(gl, &MeshData::cylinder(radius, height, sectors))
+    }
+
+    pub fn cone(gl: gl::Gl, radius: f32, height: f32, sectors: u32) -> Self {
+        Self::from_mesh_data(gl, &MeshData::cone(radius, height, sectors))
+    }
+
+    pub fn torus(
+        gl: gl::Gl,
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> Self {
+        Self::from_mesh_data(
+            gl,
+            &MeshData::torus(major_radius, minor_radius, major_segments, minor_segments),
+        )
+    }
+
+    pub fn capsule(gl: gl::Gl, radius: f32, height: f32, sectors: u32, stacks: u32) -> Self {
+        Self::from_mesh_data(gl, &MeshData::capsule(radius, height, sectors, stacks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shapes() -> Vec<(&'static str, MeshData)> {
+        vec![
+            ("cube", MeshData::cube()),
+            ("uv_sphere", MeshData::uv_sphere(1.0, 16, 8)),
+            ("icosphere", MeshData::icosphere(1.0, 2)),
+            ("plane", MeshData::plane(2.0, 3.0, 4, 5)),
+            ("cylinder", MeshData::cylinder(1.0, 2.0, 12)),
+            ("cone", MeshData::cone(1.0, 2.0, 12)),
+            ("torus", MeshData::torus(1.0, 0.25, 16, 8)),
+            ("capsule", MeshData::capsule(0.5, 1.0, 12, 4)),
+        ]
+    }
+
+    #[test]
+    fn unit_normals() {
+        for (name, data) in shapes() {
+            for vertex in data.vertices.iter() {
+                let length = vertex.normal().magnitude();
+                assert!((length - 1.0).abs() < 1e-4, "{}: {}", name, length);
+            }
+        }
+    }
+
+    #[test]
+    fn faces_point_along_normals() {
+        for (name, data) in shapes() {
+            assert!(!data.indices.is_empty());
+            for triangle in data.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| &data.vertices[triangle[i] as usize]);
+                let face = (b.position() - a.position()).cross(c.position() - a.position());
+                // Triangles collapsed at the poles and apexes have no direction.
+                if face.magnitude() < 1e-6 {
+                    continue;
+                }
+                let normals = a.normal() + b.normal() + c.normal();
+                assert!(face.dot(normals) > 0.0, "{}: {:?}", name, triangle);
+            }
+        }
+    }
+
+    #[test]
+    fn texture_coordinates_in_range() {
+        for (name, data) in shapes() {
+            for vertex in data.vertices.iter() {
+                let (u, v) = vertex.texture_coordinates();
+                // The icosphere repeats the texture past 1 on the triangles crossing its seam.
+                let max_u = if name == "icosphere" { 1.5 } else { 1.0 };
+                assert!((0.0..=max_u + 1e-6).contains(&u), "{}: u = {}", name, u);
+                assert!((0.0..=1.0 + 1e-6).contains(&v), "{}: v = {}", name, v);
+            }
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        let on_sphere = |data: &MeshData, radius: f32| {
+            data.vertices
+                .iter()
+                .all(|v| (v.position().magnitude() - radius).abs() < 1e-4)
+        };
+        assert!(on_sphere(&MeshData::uv_sphere(2.0, 8, 4), 2.0));
+        assert!(on_sphere(&MeshData::icosphere(3.0, 1), 3.0));
+        assert_eq!(MeshData::icosphere(1.0, 1).indices.len(), 80 * 3);
+
+        let cube = MeshData::cube();
+        assert_eq!(cube.aabb().max, Vector3::new(0.5, 0.5, 0.5));
+        assert!((cube.bounding_sphere().radius - 0.75f32.sqrt()).abs() < 1e-6);
+
+        let capsule = MeshData::capsule(0.5, 1.0, 8, 4);
+        let top = capsule
+            .vertices
+            .iter()
+            .map(|v| v.position().y)
+            .fold(f32::MIN, f32::max);
+        assert!((top - 1.0).abs() < 1e-6);
+    }
+}
@@ -1,8 +1,8 @@
-use crate::cgmath::{Matrix, Point3, Vector3};
-use gl::types::{GLint, GLuint};
+use crate::cgmath::{Matrix, Point3, Vector3, Vector4};
+use gl::types::{GLfloat, GLint, GLsizei, GLuint};
 use gl::Gl;
 
-use super::{create_whitespace_cstring, Shader};
+use super::{create_whitespace_cstring, Error, Shader};
 
 pub struct Program {
     gl: Gl,
@@ -14,7 +14,7 @@ impl Program {
         self.id
     }
 
-    pub fn new(gl: Gl, shaders: &[Shader]) -> Result<Program, String> {
+    pub fn new(gl: Gl, shaders: &[Shader]) -> Result<Program, Error> {
         let id = unsafe { gl.CreateProgram() };
         for shader in shaders {
             unsafe {
@@ -53,7 +53,13 @@ impl Program {
                 );
             }
 
-            return Err(error.to_string_lossy().into_owned());
+            unsafe {
+                gl.DeleteProgram(id);
+            }
+
+            return Err(Error::ProgramLink {
+                log: error.to_string_lossy().into_owned(),
+            });
         }
         Ok(Self { gl, id })
     }
@@ -64,7 +70,7 @@ impl Program {
         }
     }
 
-    pub fn set_matrix4(&self, name: &str, matrix: &cgmath::Matrix4<f32>) -> Result<(), String> {
+    pub fn set_matrix4(&self, name: &str, matrix: &cgmath::Matrix4<f32>) -> Result<(), Error> {
         let uniform_id = self.get_uniform_location(name)?;
 
         unsafe {
@@ -76,7 +82,28 @@ impl Program {
         Ok(())
     }
 
-    pub fn set_vec3(&self, name: &str, value: Vector3<f32>) -> Result<(), String> {
+    /// Sets `name[0]` and the following elements of a `mat4` array.
+    pub fn set_matrix4_array(
+        &self,
+        name: &str,
+        matrices: &[cgmath::Matrix4<f32>],
+    ) -> Result<(), Error> {
+        let uniform_id = self.get_uniform_location(name)?;
+
+        unsafe {
+            self.gl.UseProgram(self.id);
+            self.gl.UniformMatrix4fv(
+                uniform_id,
+                matrices.len() as GLsizei,
+                gl::FALSE,
+                matrices.as_ptr() as *const GLfloat,
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn set_vec3(&self, name: &str, value: Vector3<f32>) -> Result<(), Error> {
         let uniform_id = self.get_uniform_location(name)?;
 
         unsafe {
@@ -87,7 +114,19 @@ impl Program {
         Ok(())
     }
 
-    pub fn set_float(&self, name: &str, value: f32) -> Result<(), String> {
+    pub fn set_vec4(&self, name: &str, value: Vector4<f32>) -> Result<(), Error> {
+        let uniform_id = self.get_uniform_location(name)?;
+
+        unsafe {
+            self.gl.UseProgram(self.id);
+            self.gl
+                .Uniform4f(uniform_id, value.x, value.y, value.z, value.w);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_float(&self, name: &str, value: f32) -> Result<(), Error> {
         let uniform_id = self.get_uniform_location(name)?;
 
         unsafe {
@@ -98,7 +137,7 @@ impl Program {
         Ok(())
     }
 
-    pub fn set_point3(&self, name: &str, value: Point3<f32>) -> Result<(), String> {
+    pub fn set_point3(&self, name: &str, value: Point3<f32>) -> Result<(), Error> {
         let uniform_id = self.get_uniform_location(name)?;
 
         unsafe {
@@ -109,7 +148,7 @@ impl Program {
         Ok(())
     }
 
-    pub fn set_int(&self, name: &str, value: i32) -> Result<(), String> {
+    pub fn set_int(&self, name: &str, value: i32) -> Result<(), Error> {
         let uniform_id = self.get_uniform_location(name)?;
 
         unsafe {
@@ -120,7 +159,7 @@ impl Program {
         Ok(())
     }
 
-    fn get_uniform_location(&self, name: &str) -> Result<GLint, String> {
+    fn get_uniform_location(&self, name: &str) -> Result<GLint, Error> {
         let c_name = std::ffi::CString::new(name).unwrap();
         let uniform_id = unsafe {
             self.gl
@@ -128,13 +167,31 @@ impl Program {
         };
 
         if uniform_id == -1 {
-            return Err(format!("Could not find uniform {} in program", name));
+            return Err(Error::MissingUniform {
+                name: name.to_string(),
+            });
         }
 
         Ok(uniform_id)
     }
 }
 
+/// Shader compilers strip uniforms that do not affect the output, so which ones a program has
+/// depends on its shaders.
+pub trait UniformResult {
+    /// Treats a uniform the program does not have as set, for uniforms only some shaders use.
+    fn or_stripped(self) -> Result<(), Error>;
+}
+
+impl UniformResult for Result<(), Error> {
+    fn or_stripped(self) -> Result<(), Error> {
+        match self {
+            Err(Error::MissingUniform { .. }) => Ok(()),
+            result => result,
+        }
+    }
+}
+
 impl Drop for Program {
     fn drop(&mut self) {
         unsafe {
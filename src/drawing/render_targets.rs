@@ -0,0 +1,333 @@
+use gl::{
+    types::{GLbitfield, GLenum, GLint, GLuint},
+    Gl,
+};
+use std::{cell::Cell, rc::Rc};
+
+use super::{
+    objects::{Framebuffer, Renderbuffer},
+    textures::PixelFormat,
+    Error, Texture, TextureOptions, Wrap,
+};
+
+/// What an attachment of a `RenderTarget` keeps its pixels in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Storage {
+    /// Can be sampled afterwards, e.g. for post-processing or shadow maps.
+    Texture,
+    /// Cheaper when the pixels are only blitted or only used for depth testing.
+    Renderbuffer,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttachmentFormat {
+    pub format: PixelFormat,
+    pub storage: Storage,
+}
+
+impl AttachmentFormat {
+    pub fn texture(format: PixelFormat) -> Self {
+        Self {
+            format,
+            storage: Storage::Texture,
+        }
+    }
+
+    pub fn renderbuffer(format: PixelFormat) -> Self {
+        Self {
+            format,
+            storage: Storage::Renderbuffer,
+        }
+    }
+}
+
+enum Attachment {
+    Texture(Rc<Texture>),
+    Renderbuffer(Renderbuffer),
+}
+
+impl Attachment {
+    fn new(gl: &Gl, format: AttachmentFormat, width: u32, height: u32) -> Self {
+        match format.storage {
+            Storage::Texture => {
+                let options = TextureOptions::default()
+                    .without_mipmaps()
+                    .with_wrap(Wrap::ClampToEdge);
+                let texture = Texture::empty(gl.clone(), width, height, format.format, options);
+                Attachment::Texture(Rc::new(texture))
+            }
+            Storage::Renderbuffer => {
+                let renderbuffer = Renderbuffer::new(gl.clone());
+                renderbuffer.allocate(format.format.internal_format(), width, height);
+                Attachment::Renderbuffer(renderbuffer)
+            }
+        }
+    }
+
+    fn attach(&self, framebuffer: &Framebuffer, point: GLenum) {
+        match self {
+            Attachment::Texture(texture) => framebuffer.attach_texture(point, texture.id),
+            Attachment::Renderbuffer(renderbuffer) => {
+                framebuffer.attach_renderbuffer(point, renderbuffer)
+            }
+        }
+    }
+
+    fn texture(&self) -> Option<&Rc<Texture>> {
+        match self {
+            Attachment::Texture(texture) => Some(texture),
+            Attachment::Renderbuffer(_) => None,
+        }
+    }
+}
+
+/// A framebuffer together with the color and depth attachments it draws into.
+/// Texture attachments are sampled linearly, without mipmaps and clamped to the edges.
+pub struct RenderTarget {
+    gl: Gl,
+    framebuffer: Framebuffer,
+    width: u32,
+    height: u32,
+    color_formats: Vec<AttachmentFormat>,
+    depth_format: Option<AttachmentFormat>,
+    colors: Vec<Attachment>,
+    depth: Option<Attachment>,
+    /// The viewport to go back to on `unbind`.
+    previous_viewport: Cell<[GLint; 4]>,
+}
+
+impl RenderTarget {
+    /// Colors are attached from `COLOR_ATTACHMENT0` on in the order they are given, and all of
+    /// them are drawn to, `layout (location = i) out` going to the `i`th one.
+    pub fn new(
+        gl: Gl,
+        width: u32,
+        height: u32,
+        colors: &[AttachmentFormat],
+        depth: Option<AttachmentFormat>,
+    ) -> Result<Self, Error> {
+        assert!(
+            colors.iter().all(|color| !color.format.is_depth()),
+            "Color attachments cannot have depth formats"
+        );
+        assert!(
+            depth.is_none_or(|depth| depth.format.is_depth()),
+            "The depth attachment needs a depth format"
+        );
+        let mut max_colors = 0;
+        unsafe {
+            gl.GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max_colors);
+        }
+        assert!(
+            colors.len() <= max_colors as usize,
+            "{} color attachments asked for, the driver supports {}",
+            colors.len(),
+            max_colors
+        );
+
+        let mut target = Self {
+            framebuffer: Framebuffer::new(gl.clone()),
+            gl,
+            width,
+            height,
+            color_formats: colors.to_vec(),
+            depth_format: depth,
+            colors: vec![],
+            depth: None,
+            previous_viewport: Cell::new([0; 4]),
+        };
+        target.create_attachments()?;
+        Ok(target)
+    }
+
+    /// Replaces the attachments with new ones, the old textures are left to whoever still
+    /// holds them.
+    fn create_attachments(&mut self) -> Result<(), Error> {
+        let (gl, width, height) = (&self.gl, self.width, self.height);
+        self.colors = self
+            .color_formats
+            .iter()
+            .map(|&format| Attachment::new(gl, format, width, height))
+            .collect();
+        self.depth = self
+            .depth_format
+            .map(|format| Attachment::new(gl, format, width, height));
+
+        self.framebuffer.bind(gl::FRAMEBUFFER);
+        let points = color_attachment_points(self.colors.len());
+        for (color, &point) in self.colors.iter().zip(points.iter()) {
+            color.attach(&self.framebuffer, point);
+        }
+        if let (Some(depth), Some(format)) = (&self.depth, self.depth_format) {
+            depth.attach(&self.framebuffer, depth_attachment_point(format.format));
+        }
+        unsafe {
+            if points.is_empty() {
+                self.gl.DrawBuffer(gl::NONE);
+                self.gl.ReadBuffer(gl::NONE);
+            } else {
+                self.gl.DrawBuffers(points.len() as GLint, points.as_ptr());
+            }
+        }
+        let status = self.framebuffer.status();
+        unsafe {
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(Error::IncompleteFramebuffer { status });
+        }
+        Ok(())
+    }
+
+    /// Recreates every attachment in the new size, their contents are lost.
+    /// Textures got from the target before have to be got again.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        if (width, height) == (self.width, self.height) {
+            return Ok(());
+        }
+        self.width = width;
+        self.height = height;
+        self.create_attachments()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The texture of the `index`th color attachment, `None` for renderbuffers.
+    pub fn color_texture(&self, index: usize) -> Option<&Rc<Texture>> {
+        self.colors.get(index).and_then(Attachment::texture)
+    }
+
+    pub fn depth_texture(&self) -> Option<&Rc<Texture>> {
+        self.depth.as_ref().and_then(Attachment::texture)
+    }
+
+    /// Draws into the target from now on, with the viewport covering all of it.
+    pub fn bind(&self) {
+        let mut viewport = [0; 4];
+        unsafe {
+            self.gl.GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        self.previous_viewport.set(viewport);
+        self.framebuffer.bind(gl::FRAMEBUFFER);
+        unsafe {
+            self.gl
+                .Viewport(0, 0, self.width as GLint, self.height as GLint);
+        }
+    }
+
+    /// Goes back to drawing into the window, with the viewport from before `bind`.
+    pub fn unbind(&self) {
+        let [x, y, width, height] = self.previous_viewport.get();
+        unsafe {
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+            self.gl.Viewport(x, y, width, height);
+        }
+    }
+
+    /// Copies the `color`th color attachment into every color attachment of `target`,
+    /// stretching it when the sizes differ. With `depth` the depth is copied too,
+    /// which needs the same depth format on both sides.
+    pub fn blit_to(&self, target: &RenderTarget, color: usize, depth: bool) {
+        let size = (target.width, target.height);
+        self.blit(target.framebuffer.id(), color, size, gl::COLOR_BUFFER_BIT);
+        if depth {
+            self.blit(target.framebuffer.id(), color, size, gl::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    /// Copies the `color`th color attachment to the window, stretched over `width` x `height`.
+    pub fn blit_to_screen(&self, color: usize, width: u32, height: u32) {
+        self.blit(0, color, (width, height), gl::COLOR_BUFFER_BIT);
+    }
+
+    fn blit(&self, draw: GLuint, color: usize, (width, height): (u32, u32), mask: GLbitfield) {
+        assert!(
+            color < self.colors.len() || mask != gl::COLOR_BUFFER_BIT,
+            "There is no color attachment {}",
+            color
+        );
+        let filter = blit_filter((self.width, self.height), (width, height), mask);
+        self.framebuffer.bind(gl::READ_FRAMEBUFFER);
+        unsafe {
+            if mask == gl::COLOR_BUFFER_BIT {
+                self.gl.ReadBuffer(gl::COLOR_ATTACHMENT0 + color as GLenum);
+            }
+            self.gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, draw);
+            self.gl.BlitFramebuffer(
+                0,
+                0,
+                self.width as GLint,
+                self.height as GLint,
+                0,
+                0,
+                width as GLint,
+                height as GLint,
+                mask,
+                filter,
+            );
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+}
+
+fn color_attachment_points(count: usize) -> Vec<GLenum> {
+    (0..count as GLenum)
+        .map(|i| gl::COLOR_ATTACHMENT0 + i)
+        .collect()
+}
+
+fn depth_attachment_point(format: PixelFormat) -> GLenum {
+    match format {
+        PixelFormat::Depth24Stencil8 => gl::DEPTH_STENCIL_ATTACHMENT,
+        _ => gl::DEPTH_ATTACHMENT,
+    }
+}
+
+/// Stretched colors are filtered linearly, depth can only be copied as it is.
+fn blit_filter(from: (u32, u32), to: (u32, u32), mask: GLbitfield) -> GLenum {
+    if mask == gl::COLOR_BUFFER_BIT && from != to {
+        gl::LINEAR
+    } else {
+        gl::NEAREST
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attachment_points() {
+        assert_eq!(
+            color_attachment_points(3),
+            vec![
+                gl::COLOR_ATTACHMENT0,
+                gl::COLOR_ATTACHMENT1,
+                gl::COLOR_ATTACHMENT2
+            ]
+        );
+        assert!(color_attachment_points(0).is_empty());
+        assert_eq!(
+            depth_attachment_point(PixelFormat::Depth32F),
+            gl::DEPTH_ATTACHMENT
+        );
+        assert_eq!(
+            depth_attachment_point(PixelFormat::Depth24Stencil8),
+            gl::DEPTH_STENCIL_ATTACHMENT
+        );
+
+        let color = gl::COLOR_BUFFER_BIT;
+        assert_eq!(blit_filter((800, 600), (800, 600), color), gl::NEAREST);
+        assert_eq!(blit_filter((400, 300), (800, 600), color), gl::LINEAR);
+        assert_eq!(
+            blit_filter((400, 300), (800, 600), gl::DEPTH_BUFFER_BIT),
+            gl::NEAREST
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use super::{
+    gl_version, has_extension,
+    objects::{self, ObjectKind},
+};
+use gl::{
+    types::{GLenum, GLfloat, GLint, GLuint},
+    Gl,
+};
+use std::cell::OnceCell;
+
+// From ARB_texture_filter_anisotropic, core since 4.6, so the 4.5 bindings lack them.
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+/// How mipmap levels are picked for minification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MipmapFilter {
+    /// Only the full size image is sampled.
+    None,
+    Nearest,
+    /// Blends the two closest levels, trilinear filtering together with `Filter::Linear`.
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// Outside of the texture is `TextureOptions::border_color`.
+    ClampToBorder,
+}
+
+impl Wrap {
+    fn gl_enum(self) -> GLenum {
+        match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+/// How a texture is sampled. Applies to the texture itself or to a `Sampler`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureOptions {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    /// Falls back to `MipmapFilter::None` for textures without mipmaps.
+    pub mipmap_filter: MipmapFilter,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    /// Only used by cubemaps and 3D textures.
+    pub wrap_r: Wrap,
+    pub border_color: [f32; 4],
+    /// 1 turns anisotropic filtering off. Clamped to what the driver supports, ignored without support.
+    pub anisotropy: f32,
+    /// Whether textures made with these options get mipmaps. Samplers ignore it.
+    pub generate_mipmaps: bool,
+}
+
+/// Smooth trilinear filtering with repeating coordinates.
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmap_filter: MipmapFilter::Linear,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            wrap_r: Wrap::Repeat,
+            border_color: [0.0, 0.0, 0.0, 0.0],
+            anisotropy: 1.0,
+            generate_mipmaps: true,
+        }
+    }
+}
+
+impl TextureOptions {
+    /// Sharp texels up close, mipmaps still keep distant surfaces from shimmering.
+    pub fn pixel_art() -> Self {
+        Self {
+            min_filter: Filter::Nearest,
+            mag_filter: Filter::Nearest,
+            mipmap_filter: MipmapFilter::Nearest,
+            ..Self::default()
+        }
+    }
+
+    /// Trilinear filtering with as much anisotropic filtering as the driver allows.
+    pub fn smooth() -> Self {
+        Self {
+            anisotropy: 16.0,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_wrap(self, wrap: Wrap) -> Self {
+        Self {
+            wrap_s: wrap,
+            wrap_t: wrap,
+            wrap_r: wrap,
+            ..self
+        }
+    }
+
+    pub fn with_anisotropy(self, anisotropy: f32) -> Self {
+        Self { anisotropy, ..self }
+    }
+
+    pub fn without_mipmaps(self) -> Self {
+        Self {
+            mipmap_filter: MipmapFilter::None,
+            generate_mipmaps: false,
+            ..self
+        }
+    }
+
+    fn min_filter_enum(&self, has_mipmaps: bool) -> GLenum {
+        let mipmap_filter = if has_mipmaps {
+            self.mipmap_filter
+        } else {
+            MipmapFilter::None
+        };
+        match (self.min_filter, mipmap_filter) {
+            (Filter::Nearest, MipmapFilter::None) => gl::NEAREST,
+            (Filter::Linear, MipmapFilter::None) => gl::LINEAR,
+            (Filter::Nearest, MipmapFilter::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, MipmapFilter::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, MipmapFilter::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, MipmapFilter::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    fn mag_filter_enum(&self) -> GLenum {
+        match self.mag_filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        }
+    }
+
+    /// Sets every parameter on the texture bound to `target`.
+    pub(super) fn apply_to_texture(&self, gl: &Gl, target: GLenum, has_mipmaps: bool) {
+        self.apply(gl, ParameterTarget::Texture(target), has_mipmaps);
+    }
+
+    fn apply(&self, gl: &Gl, target: ParameterTarget, has_mipmaps: bool) {
+        let min_filter = self.min_filter_enum(has_mipmaps);
+        target.set_int(gl, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+        target.set_int(gl, gl::TEXTURE_MAG_FILTER, self.mag_filter_enum() as GLint);
+        target.set_int(gl, gl::TEXTURE_WRAP_S, self.wrap_s.gl_enum() as GLint);
+        target.set_int(gl, gl::TEXTURE_WRAP_T, self.wrap_t.gl_enum() as GLint);
+        target.set_int(gl, gl::TEXTURE_WRAP_R, self.wrap_r.gl_enum() as GLint);
+        target.set_floats(gl, gl::TEXTURE_BORDER_COLOR, &self.border_color);
+        // Nothing can have raised the anisotropy before support was first asked about,
+        // so going back to the default of 1 only needs the parameter when it is known.
+        let max = if self.anisotropy > 1.0 {
+            max_anisotropy(gl)
+        } else {
+            MAX_ANISOTROPY.with(|max| max.get().copied().flatten())
+        };
+        if let Some(max) = max {
+            let anisotropy = self.anisotropy.clamp(1.0, max);
+            target.set_floats(gl, TEXTURE_MAX_ANISOTROPY, &[anisotropy]);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ParameterTarget {
+    Texture(GLenum),
+    Sampler(GLuint),
+}
+
+impl ParameterTarget {
+    fn set_int(self, gl: &Gl, name: GLenum, value: GLint) {
+        unsafe {
+            match self {
+                ParameterTarget::Texture(target) => gl.TexParameteri(target, name, value),
+                ParameterTarget::Sampler(sampler) => gl.SamplerParameteri(sampler, name, value),
+            }
+        }
+    }
+
+    fn set_floats(self, gl: &Gl, name: GLenum, values: &[GLfloat]) {
+        unsafe {
+            match self {
+                ParameterTarget::Texture(target) => {
+                    gl.TexParameterfv(target, name, values.as_ptr())
+                }
+                ParameterTarget::Sampler(sampler) => {
+                    gl.SamplerParameterfv(sampler, name, values.as_ptr())
+                }
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// The GL context lives on one thread and the driver does not change, so it is asked once.
+    static MAX_ANISOTROPY: OnceCell<Option<f32>> = const { OnceCell::new() };
+}
+
+/// The highest supported anisotropy, `None` when the driver has no anisotropic filtering.
+fn max_anisotropy(gl: &Gl) -> Option<f32> {
+    MAX_ANISOTROPY.with(|max| *max.get_or_init(|| query_max_anisotropy(gl)))
+}
+
+fn query_max_anisotropy(gl: &Gl) -> Option<f32> {
+    let supported = gl_version(gl) >= (4, 6)
+        || has_extension(gl, b"GL_ARB_texture_filter_anisotropic")
+        || has_extension(gl, b"GL_EXT_texture_filter_anisotropic");
+    if !supported {
+        return None;
+    }
+    let mut max = 1.0;
+    unsafe {
+        gl.GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+    }
+    Some(max)
+}
+
+/// A GL sampler object. While bound to a texture unit it overrides the sampling
+/// parameters of whatever texture is bound there, so one texture can be sampled in several ways.
+pub struct Sampler {
+    gl: Gl,
+    id: GLuint,
+    options: TextureOptions,
+}
+
+impl Sampler {
+    /// Sampler objects need mipmaps to be there for the mipmap filters, unlike textures
+    /// they cannot fall back on their own.
+    pub fn new(gl: Gl, options: TextureOptions) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl.GenSamplers(1, &mut id);
+        }
+        objects::created(ObjectKind::Sampler);
+        options.apply(&gl, ParameterTarget::Sampler(id), true);
+        Self { gl, id, options }
+    }
+
+    pub fn options(&self) -> &TextureOptions {
+        &self.options
+    }
+
+    /// `unit` counts from 0, like `Texture::bind_n`.
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            self.gl.BindSampler(unit, self.id);
+        }
+    }
+
+    /// Gives the unit back to the parameters of its texture.
+    pub fn unbind(gl: &Gl, unit: u32) {
+        unsafe {
+            gl.BindSampler(unit, 0);
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteSamplers(1, &self.id);
+        }
+        objects::deleted(ObjectKind::Sampler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters() {
+        let smooth = TextureOptions::default();
+        assert_eq!(smooth.min_filter_enum(true), gl::LINEAR_MIPMAP_LINEAR);
+        assert_eq!(smooth.min_filter_enum(false), gl::LINEAR);
+
+        let pixels = TextureOptions::pixel_art();
+        assert_eq!(pixels.min_filter_enum(true), gl::NEAREST_MIPMAP_NEAREST);
+        assert_eq!(pixels.mag_filter_enum(), gl::NEAREST);
+
+        let flat = TextureOptions::pixel_art().without_mipmaps();
+        assert_eq!(flat.min_filter_enum(true), gl::NEAREST);
+        assert!(!flat.generate_mipmaps);
+
+        let clamped = TextureOptions::default().with_wrap(Wrap::ClampToBorder);
+        assert_eq!(clamped.wrap_t.gl_enum(), gl::CLAMP_TO_BORDER);
+    }
+}
@@ -0,0 +1,606 @@
+use cgmath::{InnerSpace, Matrix4, Quaternion, SquareMatrix, Vector3};
+use std::{collections::HashMap, path::Path, rc::Rc};
+
+use super::{
+    animations::{
+        AnimationClip, Channel, Interpolation, Joint, JointTransform, Keyframes, Skeleton, Skin,
+        SkinnedVertex, MAX_JOINTS,
+    },
+    models::{Indices, Mesh, VertexData, DEFAULT_MESH_NAME},
+    Error, Material, Model, Texture, TextureOptions,
+};
+
+/// One instance of a model placed in the scene.
+pub struct SceneNode {
+    pub name: Option<String>,
+    pub model: Rc<Model>,
+    /// Transform from the model space to the scene space, parents included.
+    /// Skinned nodes are placed by their joints alone, so theirs is the identity.
+    pub matrix: Matrix4<f32>,
+    /// Set when the model is made of `SkinnedVertex` meshes.
+    pub skin: Option<Rc<Skin>>,
+}
+
+/// Everything a glTF file brings: its models, their textures and the nodes placing them.
+pub struct Scene {
+    pub models: Vec<Rc<Model>>,
+    pub textures: Vec<Rc<Texture>>,
+    /// Skeletons with the animations that move them, the unskinned animations are skipped.
+    pub skins: Vec<Rc<Skin>>,
+    pub nodes: Vec<SceneNode>,
+}
+
+impl Scene {
+    /// Loads the default scene of a `.gltf` or `.glb` file, or the first one if there is no default.
+    pub fn from_gltf(gl: gl::Gl, file_name: &Path) -> Result<Self, Error> {
+        let (document, buffers, images) =
+            gltf::import(file_name).map_err(|source| Error::Gltf {
+                path: file_name.to_path_buf(),
+                source,
+            })?;
+
+        let textures: Vec<Rc<Texture>> = images
+            .into_iter()
+            .map(|image| {
+                let image = gltf_image_to_rgba8(image);
+                Rc::new(Texture::from_rgba8(
+                    gl.clone(),
+                    image,
+                    TextureOptions::default(),
+                ))
+            })
+            .collect();
+
+        let materials: Vec<Rc<Material>> = document
+            .materials()
+            .map(|material| Rc::new(gltf_material(&material, &textures)))
+            .collect();
+
+        let mut models = vec![];
+        for mesh in document.meshes() {
+            let name = mesh.name().unwrap_or(DEFAULT_MESH_NAME);
+            let mut meshes = vec![];
+            for primitive in mesh.primitives() {
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let (vertices, indices, sources) =
+                    primitive_vertices(gltf_attributes(&reader), primitive.mode()).map_err(
+                        |message| {
+                            Error::invalid_data(
+                                file_name,
+                                format!(
+                                    "mesh {} primitive {}: {}",
+                                    mesh.index(),
+                                    primitive.index(),
+                                    message
+                                ),
+                            )
+                        },
+                    )?;
+                let vertex_number = vertices.len();
+                let indices = Indices::new(indices, vertex_number);
+                let mode = primitive.mode().as_gl_enum();
+                let joints = reader
+                    .read_joints(0)
+                    .map(|j| j.into_u16().collect::<Vec<_>>());
+                let weights = reader
+                    .read_weights(0)
+                    .map(|w| w.into_f32().collect::<Vec<_>>());
+                let mut mesh = match (joints, weights) {
+                    (Some(joints), Some(weights)) => {
+                        let count = sources.iter().max().map_or(0, |source| source + 1);
+                        if joints.len() < count || weights.len() < count {
+                            return Err(Error::invalid_data(
+                                file_name,
+                                format!(
+                                    "mesh {} primitive {}: {} joints and {} weights for {} vertices",
+                                    mesh.index(),
+                                    primitive.index(),
+                                    joints.len(),
+                                    weights.len(),
+                                    count
+                                ),
+                            ));
+                        }
+                        let vertices = skinned_vertices(&vertices, &sources, &joints, &weights);
+                        Mesh::with_indices(gl.clone(), name, &vertices, indices, mode)
+                    }
+                    _ => Mesh::with_indices(gl.clone(), name, &vertices, indices, mode),
+                };
+                mesh.set_material(
+                    primitive
+                        .material()
+                        .index()
+                        .map(|index| materials[index].clone()),
+                );
+                meshes.push(mesh);
+            }
+            models.push(Rc::new(Model::from_meshes(meshes)));
+        }
+
+        let mut skins = vec![];
+        for skin in document.skins() {
+            let joint_count = skin.joints().count();
+            if joint_count > MAX_JOINTS {
+                return Err(Error::invalid_data(
+                    file_name,
+                    format!(
+                        "skin {} has {} joints, at most {} are supported",
+                        skin.index(),
+                        joint_count,
+                        MAX_JOINTS
+                    ),
+                ));
+            }
+            let skin = gltf_skin(&document, &skin, &buffers)
+                .map_err(|message| Error::invalid_data(file_name, message))?;
+            skins.push(Rc::new(skin));
+        }
+
+        let mut nodes = vec![];
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next());
+        if let Some(scene) = scene {
+            for node in scene.nodes() {
+                collect_nodes(&node, Matrix4::identity(), &models, &skins, &mut nodes);
+            }
+        }
+
+        Ok(Self {
+            models,
+            textures,
+            skins,
+            nodes,
+        })
+    }
+}
+
+fn collect_nodes(
+    node: &gltf::Node,
+    parent: Matrix4<f32>,
+    models: &[Rc<Model>],
+    skins: &[Rc<Skin>],
+    nodes: &mut Vec<SceneNode>,
+) {
+    let matrix = parent * Matrix4::from(node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        let skin = node.skin().map(|skin| skins[skin.index()].clone());
+        nodes.push(SceneNode {
+            name: node.name().map(|name| name.to_string()),
+            model: models[mesh.index()].clone(),
+            matrix: if skin.is_some() {
+                Matrix4::identity()
+            } else {
+                matrix
+            },
+            skin,
+        });
+    }
+    for child in node.children() {
+        collect_nodes(&child, matrix, models, skins, nodes);
+    }
+}
+
+fn gltf_skin(
+    document: &gltf::Document,
+    skin: &gltf::Skin,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Skin, String> {
+    // glTF only links nodes to their children.
+    let mut parents = vec![None; document.nodes().count()];
+    for node in document.nodes() {
+        for child in node.children() {
+            parents[child.index()] = Some(node.index());
+        }
+    }
+    let locals: Vec<Matrix4<f32>> = document
+        .nodes()
+        .map(|node| Matrix4::from(node.transform().matrix()))
+        .collect();
+    let ancestors = |mut index: usize| {
+        let mut matrix = Matrix4::identity();
+        while let Some(parent) = parents[index] {
+            matrix = locals[parent] * matrix;
+            index = parent;
+        }
+        matrix
+    };
+
+    let joint_of: HashMap<usize, usize> = skin
+        .joints()
+        .enumerate()
+        .map(|(joint, node)| (node.index(), joint))
+        .collect();
+    let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+    let mut inverse_binds: Vec<Matrix4<f32>> = match reader.read_inverse_bind_matrices() {
+        Some(matrices) => matrices.map(Matrix4::from).collect(),
+        None => vec![],
+    };
+    inverse_binds.resize(joint_of.len(), Matrix4::identity());
+
+    let joints = skin
+        .joints()
+        .zip(inverse_binds)
+        .map(|(node, inverse_bind)| {
+            let parent = parents[node.index()].and_then(|parent| joint_of.get(&parent).cloned());
+            let (translation, [x, y, z, w], scale) = node.transform().decomposed();
+            Joint {
+                name: node.name().map(|name| name.to_string()),
+                parent,
+                origin: match parent {
+                    Some(_) => Matrix4::identity(),
+                    None => ancestors(node.index()),
+                },
+                inverse_bind,
+                rest: JointTransform {
+                    translation: translation.into(),
+                    rotation: Quaternion::new(w, x, y, z),
+                    scale: scale.into(),
+                },
+            }
+        })
+        .collect();
+
+    let mut animations = vec![];
+    for animation in document.animations() {
+        let mut channels = vec![];
+        for channel in animation.channels() {
+            if let Some(channel) = gltf_channel(&channel, &joint_of, buffers) {
+                channel
+                    .check()
+                    .map_err(|message| format!("animation {}: {}", animation.index(), message))?;
+                channels.push(channel);
+            }
+        }
+        if !channels.is_empty() {
+            let name = animation.name().map(|name| name.to_string());
+            animations.push(AnimationClip::new(name, channels));
+        }
+    }
+
+    Ok(Skin {
+        skeleton: Skeleton::new(joints),
+        animations,
+    })
+}
+
+/// `None` for channels that do not move a joint of the skin.
+fn gltf_channel(
+    channel: &gltf::animation::Channel,
+    joint_of: &HashMap<usize, usize>,
+    buffers: &[gltf::buffer::Data],
+) -> Option<Channel> {
+    use gltf::animation::util::ReadOutputs;
+
+    let joint = *joint_of.get(&channel.target().node().index())?;
+    let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+    let times: Vec<f32> = reader.read_inputs()?.collect();
+    let keyframes = match reader.read_outputs()? {
+        ReadOutputs::Translations(values) => {
+            Keyframes::Translation(values.map(Vector3::from).collect())
+        }
+        ReadOutputs::Rotations(values) => Keyframes::Rotation(
+            values
+                .into_f32()
+                .map(|[x, y, z, w]| Quaternion::new(w, x, y, z))
+                .collect(),
+        ),
+        ReadOutputs::Scales(values) => Keyframes::Scale(values.map(Vector3::from).collect()),
+        ReadOutputs::MorphTargetWeights(_) => return None,
+    };
+    let interpolation = match channel.sampler().interpolation() {
+        gltf::animation::Interpolation::Step => Interpolation::Step,
+        gltf::animation::Interpolation::Linear => Interpolation::Linear,
+        gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+    };
+    Some(Channel {
+        joint,
+        times,
+        keyframes,
+        interpolation,
+    })
+}
+
+/// glTF materials are physically based, so this is only an approximation of them.
+fn gltf_material(material: &gltf::Material, textures: &[Rc<Texture>]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, _] = pbr.base_color_factor();
+    let specular = 1.0 - pbr.roughness_factor();
+
+    let mut result = Material::new(material.name().unwrap_or("gltf"));
+    result.ambient = (r, g, b).into();
+    result.diffuse = (r, g, b).into();
+    result.specular = (specular, specular, specular).into();
+    if let Some(info) = pbr.base_color_texture() {
+        result.set_diffuse_texture(textures[info.texture().source().index()].clone());
+    }
+    result
+}
+
+/// The vertex attributes and indices of one glTF primitive, `None` where the file has none.
+struct Attributes {
+    positions: Vec<[f32; 3]>,
+    texture_coordinates: Option<Vec<[f32; 2]>>,
+    colors: Option<Vec<[f32; 3]>>,
+    normals: Option<Vec<[f32; 3]>>,
+    indices: Option<Vec<u32>>,
+}
+
+fn gltf_attributes<'a, 's, F>(reader: &gltf::mesh::Reader<'a, 's, F>) -> Attributes
+where
+    F: Clone + Fn(gltf::Buffer<'a>) -> Option<&'s [u8]>,
+{
+    Attributes {
+        positions: match reader.read_positions() {
+            Some(positions) => positions.collect(),
+            None => vec![],
+        },
+        texture_coordinates: reader
+            .read_tex_coords(0)
+            .map(|coordinates| coordinates.into_f32().collect()),
+        colors: reader
+            .read_colors(0)
+            .map(|colors| colors.into_rgb_f32().collect()),
+        normals: reader.read_normals().map(|normals| normals.collect()),
+        indices: reader
+            .read_indices()
+            .map(|indices| indices.into_u32().collect()),
+    }
+}
+
+/// Vertices, indices and the glTF vertex every vertex was made from, they differ once flat
+/// normals are added.
+type PrimitiveVertices = (Vec<VertexData>, Vec<u32>, Vec<usize>);
+
+/// Attributes that do not have a value for every position and indices past the last position
+/// are errors.
+fn primitive_vertices(
+    attributes: Attributes,
+    mode: gltf::mesh::Mode,
+) -> Result<PrimitiveVertices, String> {
+    let Attributes {
+        positions,
+        texture_coordinates,
+        colors,
+        normals,
+        indices,
+    } = attributes;
+    let count = positions.len();
+    let check = |name: &str, len: Option<usize>| match len {
+        Some(len) if len != count => Err(format!("{} {} for {} positions", len, name, count)),
+        _ => Ok(()),
+    };
+    check(
+        "texture coordinates",
+        texture_coordinates.as_ref().map(Vec::len),
+    )?;
+    check("colors", colors.as_ref().map(Vec::len))?;
+    check("normals", normals.as_ref().map(Vec::len))?;
+    let texture_coordinates = texture_coordinates.unwrap_or_else(|| vec![[0.0, 0.0]; count]);
+    let colors = colors.unwrap_or_else(|| vec![[1.0, 1.0, 1.0]; count]);
+    let mut indices = indices.unwrap_or_else(|| (0..count as u32).collect());
+    if let Some(&index) = indices.iter().find(|&&i| i as usize >= count) {
+        return Err(format!(
+            "Index {} is out of range, only {} vertices are defined",
+            index, count
+        ));
+    }
+
+    let normals = match normals {
+        Some(normals) => normals,
+        None if mode == gltf::mesh::Mode::Triangles => {
+            // The spec asks for flat normals, so every triangle gets its own vertices.
+            let flat = flat_normals(&positions, &indices);
+            let vertices = indices
+                .iter()
+                .zip(flat.iter())
+                .map(|(&i, normal)| {
+                    let i = i as usize;
+                    VertexData::new(positions[i], texture_coordinates[i], colors[i], *normal)
+                })
+                .collect();
+            let sources = indices.iter().map(|&i| i as usize).collect();
+            indices = (0..indices.len() as u32).collect();
+            return Ok((vertices, indices, sources));
+        }
+        None => vec![[0.0, 0.0, 0.0]; count],
+    };
+
+    let vertices = (0..count)
+        .map(|i| VertexData::new(positions[i], texture_coordinates[i], colors[i], normals[i]))
+        .collect();
+    Ok((vertices, indices, (0..count).collect()))
+}
+
+/// `sources` tells which glTF vertex every vertex was made from, see `primitive_vertices`.
+fn skinned_vertices(
+    vertices: &[VertexData],
+    sources: &[usize],
+    joints: &[[u16; 4]],
+    weights: &[[f32; 4]],
+) -> Vec<SkinnedVertex> {
+    vertices
+        .iter()
+        .zip(sources)
+        .map(|(vertex, &source)| {
+            let (u, v) = vertex.texture_coordinates();
+            SkinnedVertex {
+                position: vertex.position().into(),
+                color: vertex.color().into(),
+                texture_coordinates: [u, v],
+                normal: vertex.normal().into(),
+                joints: joints[source],
+                weights: weights[source],
+            }
+        })
+        .collect()
+}
+
+fn flat_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = Vec::with_capacity(indices.len());
+    for triangle in indices.chunks(3) {
+        let normal = match triangle {
+            [a, b, c] => {
+                let a = Vector3::from(positions[*a as usize]);
+                let b = Vector3::from(positions[*b as usize]);
+                let c = Vector3::from(positions[*c as usize]);
+                let normal = (b - a).cross(c - a);
+                if normal.magnitude2() == 0.0 {
+                    normal
+                } else {
+                    normal.normalize()
+                }
+            }
+            _ => Vector3::new(0.0, 0.0, 0.0),
+        };
+        for _ in triangle {
+            normals.push(normal.into());
+        }
+    }
+    normals
+}
+
+fn gltf_image_to_rgba8(image: gltf::image::Data) -> image::RgbaImage {
+    use gltf::image::Format;
+
+    let channels = match image.format {
+        Format::R8 | Format::R16 => 1,
+        Format::R8G8 | Format::R16G16 => 2,
+        Format::R8G8B8 | Format::B8G8R8 | Format::R16G16B16 => 3,
+        Format::R8G8B8A8 | Format::B8G8R8A8 | Format::R16G16B16A16 => 4,
+    };
+    let wide = matches!(
+        image.format,
+        Format::R16 | Format::R16G16 | Format::R16G16B16 | Format::R16G16B16A16
+    );
+    let bgr = matches!(image.format, Format::B8G8R8 | Format::B8G8R8A8);
+
+    // 16 bit channels are little endian, their high byte is enough for RGBA8.
+    let bytes: Vec<u8> = if wide {
+        image.pixels.chunks(2).map(|c| c[1]).collect()
+    } else {
+        image.pixels
+    };
+    let mut rgba = Vec::with_capacity((image.width * image.height * 4) as usize);
+    for pixel in bytes.chunks(channels) {
+        let (r, g, b, a) = match pixel {
+            [r] => (*r, *r, *r, 255),
+            [r, g] => (*r, *g, 0, 255),
+            [r, g, b] => (*r, *g, *b, 255),
+            [r, g, b, a] => (*r, *g, *b, *a),
+            _ => (0, 0, 0, 255),
+        };
+        if bgr {
+            rgba.extend_from_slice(&[b, g, r, a]);
+        } else {
+            rgba.extend_from_slice(&[r, g, b, a]);
+        }
+    }
+    image::RgbaImage::from_raw(image.width, image.height, rgba).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gltf::{image::Format, mesh::Mode};
+
+    fn square() -> Attributes {
+        Attributes {
+            positions: vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ],
+            texture_coordinates: None,
+            colors: None,
+            normals: None,
+            indices: Some(vec![0, 1, 2, 2, 3, 0]),
+        }
+    }
+
+    #[test]
+    fn flat_normals_per_triangle() {
+        let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        assert_eq!(
+            flat_normals(&positions, &[0, 1, 2]),
+            vec![[0.0, 0.0, 1.0]; 3]
+        );
+        assert_eq!(
+            flat_normals(&positions, &[0, 2, 1, 0, 0, 1]),
+            vec![
+                [0.0, 0.0, -1.0],
+                [0.0, 0.0, -1.0],
+                [0.0, 0.0, -1.0],
+                [0.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn primitive_attributes() {
+        // Without normals every triangle gets vertices of its own.
+        let (vertices, indices, sources) = primitive_vertices(square(), Mode::Triangles).unwrap();
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(sources, vec![0, 1, 2, 2, 3, 0]);
+        assert_eq!(vertices[4].normal(), Vector3::new(0.0, 0.0, 1.0));
+
+        let with_normals = Attributes {
+            normals: Some(vec![[0.0, 0.0, 1.0]; 4]),
+            colors: Some(vec![[1.0, 0.0, 0.0]; 4]),
+            ..square()
+        };
+        let (vertices, indices, _) = primitive_vertices(with_normals, Mode::Triangles).unwrap();
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 2, 3, 0]);
+        assert_eq!(vertices[3].color(), Vector3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn primitive_errors() {
+        let short = Attributes {
+            texture_coordinates: Some(vec![[0.0, 0.0]; 3]),
+            ..square()
+        };
+        assert_eq!(
+            primitive_vertices(short, Mode::Triangles).err().unwrap(),
+            "3 texture coordinates for 4 positions"
+        );
+
+        let out_of_range = Attributes {
+            indices: Some(vec![0, 1, 4]),
+            ..square()
+        };
+        assert_eq!(
+            primitive_vertices(out_of_range, Mode::Triangles)
+                .err()
+                .unwrap(),
+            "Index 4 is out of range, only 4 vertices are defined"
+        );
+    }
+
+    #[test]
+    fn image_conversion() {
+        let image = |format, pixels: Vec<u8>| gltf::image::Data {
+            pixels,
+            format,
+            width: 2,
+            height: 1,
+        };
+        let rgb = gltf_image_to_rgba8(image(Format::R8G8B8, vec![1, 2, 3, 4, 5, 6]));
+        assert_eq!(rgb.into_raw(), vec![1, 2, 3, 255, 4, 5, 6, 255]);
+
+        let bgra = gltf_image_to_rgba8(image(Format::B8G8R8A8, vec![1, 2, 3, 4, 5, 6, 7, 8]));
+        assert_eq!(bgra.into_raw(), vec![3, 2, 1, 4, 7, 6, 5, 8]);
+
+        // Only the high byte of a 16 bit channel is kept.
+        let grey = gltf_image_to_rgba8(image(Format::R16, vec![0x34, 0x12, 0xFF, 0x80]));
+        assert_eq!(
+            grey.into_raw(),
+            vec![0x12, 0x12, 0x12, 255, 0x80, 0x80, 0x80, 255]
+        );
+    }
+}
@@ -2,7 +2,7 @@ use gl::types::{GLenum, GLuint};
 use gl::Gl;
 use std::ffi::CStr;
 
-use super::create_whitespace_cstring;
+use super::{create_whitespace_cstring, Error};
 
 pub struct Shader {
     gl: Gl,
@@ -14,7 +14,7 @@ impl Shader {
         self.id
     }
 
-    pub fn from_source(gl: Gl, source: &CStr, kind: GLenum) -> Result<Shader, String> {
+    pub fn from_source(gl: Gl, source: &CStr, kind: GLenum) -> Result<Shader, Error> {
         let id = unsafe { gl.CreateShader(kind) };
 
         unsafe {
@@ -44,17 +44,24 @@ impl Shader {
                 );
             }
 
-            return Err(error.to_string_lossy().into_owned());
+            unsafe {
+                gl.DeleteShader(id);
+            }
+
+            return Err(Error::ShaderCompile {
+                kind,
+                log: error.to_string_lossy().into_owned(),
+            });
         }
 
         Ok(Self { gl, id })
     }
 
-    pub fn from_vert_source(gl: Gl, source: &CStr) -> Result<Shader, String> {
+    pub fn from_vert_source(gl: Gl, source: &CStr) -> Result<Shader, Error> {
         Self::from_source(gl, source, gl::VERTEX_SHADER)
     }
 
-    pub fn from_frag_source(gl: Gl, source: &CStr) -> Result<Shader, String> {
+    pub fn from_frag_source(gl: Gl, source: &CStr) -> Result<Shader, Error> {
         Self::from_source(gl, source, gl::FRAGMENT_SHADER)
     }
 }
@@ -0,0 +1,464 @@
+use cgmath::{InnerSpace, Matrix3, SquareMatrix, Vector3};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, BinaryHeap, HashMap},
+};
+
+use super::{
+    models::{Mesh, MeshData, VertexData},
+    Model,
+};
+
+/// How much more moving an open border costs than moving across a surface.
+/// Texture seams are borders too, as their vertices are not shared.
+const BORDER_WEIGHT: f64 = 1000.0;
+
+/// The error of a point as the weighted sum of its squared distances to a set of planes.
+/// Stored as the upper triangle of a symmetric 4x4 matrix, row by row.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// The plane of points `p` with `normal.dot(p) + d == 0`, `normal` has to be unit length.
+    fn plane(normal: Vector3<f64>, d: f64, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        Quadric(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|v| v * weight),
+        )
+    }
+
+    fn add(&self, other: &Quadric) -> Self {
+        let mut sum = self.0;
+        for (value, other) in sum.iter_mut().zip(other.0.iter()) {
+            *value += other;
+        }
+        Quadric(sum)
+    }
+
+    fn error(&self, p: Vector3<f64>) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+
+    /// The point with the smallest error, unless the planes leave a whole line or plane of them.
+    fn minimum(&self) -> Option<Vector3<f64>> {
+        let q = &self.0;
+        #[rustfmt::skip]
+        let matrix = Matrix3::new(
+            q[0], q[1], q[2],
+            q[1], q[4], q[5],
+            q[2], q[5], q[7],
+        );
+        matrix
+            .invert()
+            .map(|inverse| inverse * Vector3::new(-q[3], -q[6], -q[8]))
+    }
+}
+
+/// Collapsing `removed` into `kept`, ordered by the smallest cost first.
+struct Collapse {
+    cost: f64,
+    kept: usize,
+    removed: usize,
+    /// The versions of both vertices when the cost was calculated.
+    versions: (u32, u32),
+    position: Vector3<f64>,
+    /// Where `position` lies between `kept` (0) and `removed` (1), to interpolate the other attributes.
+    t: f32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, `BinaryHeap` pops the largest element.
+        // Ties go by the vertices, so the result does not depend on the order of the queue.
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| (other.kept, other.removed).cmp(&(self.kept, self.removed)))
+    }
+}
+
+struct Simplifier {
+    vertices: Vec<VertexData>,
+    positions: Vec<Vector3<f64>>,
+    quadrics: Vec<Quadric>,
+    /// Bumped on every change of a vertex, `u32::MAX` once it is collapsed away.
+    versions: Vec<u32>,
+    triangles: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    alive_number: usize,
+    /// Triangles around every vertex, dead ones included until the vertex changes.
+    around: Vec<Vec<usize>>,
+}
+
+impl Simplifier {
+    fn new(data: &MeshData) -> Self {
+        let vertex_number = data.vertices.len();
+        let positions: Vec<Vector3<f64>> = data
+            .vertices
+            .iter()
+            .map(|v| v.position().cast().unwrap())
+            .collect();
+        let triangles: Vec<[usize; 3]> = data
+            .indices
+            .chunks_exact(3)
+            .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+            .filter(|[a, b, c]| a != b && b != c && c != a)
+            .collect();
+
+        let mut around = vec![vec![]; vertex_number];
+        let mut quadrics = vec![Quadric::default(); vertex_number];
+        let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+        for (index, triangle) in triangles.iter().enumerate() {
+            for (i, &vertex) in triangle.iter().enumerate() {
+                around[vertex].push(index);
+                let next = triangle[(i + 1) % 3];
+                *edges
+                    .entry((vertex.min(next), vertex.max(next)))
+                    .or_insert(0) += 1;
+            }
+            let normal = face_normal(&positions, triangle);
+            let area = normal.magnitude() / 2.0;
+            if area == 0.0 {
+                continue;
+            }
+            let normal = normal.normalize();
+            let plane = Quadric::plane(normal, -normal.dot(positions[triangle[0]]), area);
+            for &vertex in triangle.iter() {
+                quadrics[vertex] = quadrics[vertex].add(&plane);
+            }
+        }
+
+        // Borders get planes standing on them, so they only move along themselves.
+        for triangle in triangles.iter() {
+            let normal = face_normal(&positions, triangle);
+            if normal.magnitude2() == 0.0 {
+                continue;
+            }
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                if edges[&(a.min(b), a.max(b))] != 1 {
+                    continue;
+                }
+                let edge = positions[b] - positions[a];
+                let border = edge.cross(normal).normalize();
+                let plane = Quadric::plane(
+                    border,
+                    -border.dot(positions[a]),
+                    BORDER_WEIGHT * edge.magnitude2(),
+                );
+                quadrics[a] = quadrics[a].add(&plane);
+                quadrics[b] = quadrics[b].add(&plane);
+            }
+        }
+
+        Self {
+            vertices: data.vertices.clone(),
+            positions,
+            quadrics,
+            versions: vec![0; vertex_number],
+            alive: vec![true; triangles.len()],
+            alive_number: triangles.len(),
+            triangles,
+            around,
+        }
+    }
+
+    fn collapse_cost(&self, kept: usize, removed: usize) -> Collapse {
+        let quadric = self.quadrics[kept].add(&self.quadrics[removed]);
+        let (a, b) = (self.positions[kept], self.positions[removed]);
+        let middle = (a + b) / 2.0;
+        let mut options = vec![(a, 0.0), (b, 1.0), (middle, 0.5)];
+        if let Some(minimum) = quadric.minimum() {
+            // Nearly flat neighbourhoods put the minimum far away, where it makes no sense.
+            let edge = b - a;
+            if edge.magnitude2() > 0.0 && (minimum - middle).magnitude2() <= edge.magnitude2() {
+                let t = (minimum - a).dot(edge) / edge.magnitude2();
+                options.push((minimum, t.clamp(0.0, 1.0)));
+            }
+        }
+        let (position, t, cost) = options
+            .into_iter()
+            .map(|(position, t)| (position, t, quadric.error(position)))
+            .min_by(|x, y| x.2.total_cmp(&y.2))
+            .unwrap();
+        Collapse {
+            cost,
+            kept,
+            removed,
+            versions: (self.versions[kept], self.versions[removed]),
+            position,
+            t: t as f32,
+        }
+    }
+
+    fn is_current(&self, collapse: &Collapse) -> bool {
+        (
+            self.versions[collapse.kept],
+            self.versions[collapse.removed],
+        ) == collapse.versions
+    }
+
+    /// Whether moving both vertices to the new position turns any remaining triangle around.
+    fn flips(&self, collapse: &Collapse) -> bool {
+        for &vertex in [collapse.kept, collapse.removed].iter() {
+            for &index in self.around[vertex].iter() {
+                let triangle = self.triangles[index];
+                if !self.alive[index]
+                    || (triangle.contains(&collapse.kept) && triangle.contains(&collapse.removed))
+                {
+                    continue;
+                }
+                let before = face_normal(&self.positions, &triangle);
+                let mut positions = triangle.map(|i| self.positions[i]);
+                for (i, &corner) in triangle.iter().enumerate() {
+                    if corner == vertex {
+                        positions[i] = collapse.position;
+                    }
+                }
+                let after = (positions[1] - positions[0]).cross(positions[2] - positions[0]);
+                if before.dot(after) <= 0.0 {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn collapse(&mut self, collapse: &Collapse) -> Vec<usize> {
+        let (kept, removed) = (collapse.kept, collapse.removed);
+        self.vertices[kept] = interpolate(
+            &self.vertices[kept],
+            &self.vertices[removed],
+            collapse.t,
+            collapse.position,
+        );
+        self.positions[kept] = collapse.position;
+        self.quadrics[kept] = self.quadrics[kept].add(&self.quadrics[removed]);
+        self.versions[kept] += 1;
+        self.versions[removed] = u32::MAX;
+
+        for index in std::mem::take(&mut self.around[removed]) {
+            if !self.alive[index] {
+                continue;
+            }
+            let triangle = &mut self.triangles[index];
+            if triangle.contains(&kept) {
+                self.alive[index] = false;
+                self.alive_number -= 1;
+            } else {
+                for corner in triangle.iter_mut() {
+                    if *corner == removed {
+                        *corner = kept;
+                    }
+                }
+                self.around[kept].push(index);
+            }
+        }
+        let alive = &self.alive;
+        self.around[kept].retain(|index| alive[*index]);
+
+        let mut neighbours = vec![];
+        for &index in self.around[kept].iter() {
+            for &corner in self.triangles[index].iter() {
+                if corner != kept && !neighbours.contains(&corner) {
+                    neighbours.push(corner);
+                }
+            }
+        }
+        neighbours
+    }
+
+    fn into_mesh_data(self) -> MeshData {
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+        let mut data = MeshData::default();
+        for (triangle, _) in self
+            .triangles
+            .iter()
+            .zip(self.alive.iter())
+            .filter(|(_, alive)| **alive)
+        {
+            for &corner in triangle.iter() {
+                if remap[corner] == u32::MAX {
+                    remap[corner] = data.vertices.len() as u32;
+                    data.vertices.push(self.vertices[corner].clone());
+                }
+                data.indices.push(remap[corner]);
+            }
+        }
+        data
+    }
+}
+
+fn face_normal(positions: &[Vector3<f64>], triangle: &[usize; 3]) -> Vector3<f64> {
+    let [a, b, c] = triangle.map(|i| positions[i]);
+    (b - a).cross(c - a)
+}
+
+fn interpolate(a: &VertexData, b: &VertexData, t: f32, position: Vector3<f64>) -> VertexData {
+    let (a_texture, b_texture) = (a.texture_coordinates(), b.texture_coordinates());
+    let color = a.color() + (b.color() - a.color()) * t;
+    let mut normal = a.normal() + (b.normal() - a.normal()) * t;
+    if normal.magnitude2() != 0.0 {
+        normal = normal.normalize();
+    }
+    VertexData::new(
+        position.cast().unwrap().into(),
+        [
+            a_texture.0 + (b_texture.0 - a_texture.0) * t,
+            a_texture.1 + (b_texture.1 - a_texture.1) * t,
+        ],
+        color.into(),
+        normal.into(),
+    )
+}
+
+impl MeshData {
+    /// Collapses edges with the smallest quadric error until at most `target_triangles` are left,
+    /// or until every remaining collapse would turn a triangle around.
+    /// Open borders and texture seams stay in place.
+    pub fn simplify(&self, target_triangles: usize) -> MeshData {
+        let mut simplifier = Simplifier::new(self);
+        let mut edges = BTreeSet::new();
+        for triangle in simplifier.triangles.iter() {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                edges.insert((a.min(b), a.max(b)));
+            }
+        }
+        let mut queue: BinaryHeap<Collapse> = edges
+            .into_iter()
+            .map(|(a, b)| simplifier.collapse_cost(a, b))
+            .collect();
+
+        while simplifier.alive_number > target_triangles {
+            let collapse = match queue.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+            if !simplifier.is_current(&collapse) || simplifier.flips(&collapse) {
+                continue;
+            }
+            for neighbour in simplifier.collapse(&collapse) {
+                queue.push(simplifier.collapse_cost(collapse.kept, neighbour));
+            }
+        }
+        simplifier.into_mesh_data()
+    }
+
+    /// Levels of detail starting with a copy of `self`, each with about `ratio` of the triangles
+    /// of the previous one. The chain ends early when a level can not be simplified further.
+    pub fn lod_chain(&self, levels: usize, ratio: f32) -> Vec<MeshData> {
+        let mut chain = vec![self.clone()];
+        while chain.len() < levels {
+            let last = chain.last().unwrap();
+            let triangles = last.indices.len() / 3;
+            let next = last.simplify((triangles as f32 * ratio) as usize);
+            if next.indices.len() / 3 >= triangles {
+                break;
+            }
+            chain.push(next);
+        }
+        chain
+    }
+}
+
+impl Model {
+    /// A copy with every mesh simplified to about `ratio` of its triangles, keeping names and materials.
+    /// Needs the geometry of every mesh, see `Model::from_retained_mesh_data` and `ObjOptions::keep_mesh_data`.
+    pub fn simplified(&self, gl: gl::Gl, ratio: f32) -> Option<Model> {
+        let mut meshes = vec![];
+        for mesh in self.meshes() {
+            let data = mesh.data()?;
+            let target = (data.indices.len() as f32 / 3.0 * ratio) as usize;
+            let mut simplified = Mesh::from_data(
+                gl.clone(),
+                mesh.name(),
+                data.simplify(target),
+                gl::TRIANGLES,
+                true,
+            );
+            simplified.set_material(mesh.material().cloned());
+            meshes.push(simplified);
+        }
+        Some(Model::from_meshes(meshes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_grid_collapses_to_corners() {
+        let grid = MeshData::plane(2.0, 2.0, 8, 8);
+        let simplified = grid.simplify(2);
+        assert_eq!(simplified.indices.len(), 6);
+        for vertex in simplified.vertices.iter() {
+            let p = vertex.position();
+            assert!((p.x.abs() - 1.0).abs() < 1e-4 && (p.z.abs() - 1.0).abs() < 1e-4);
+            assert!(p.y.abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn sphere_keeps_its_shape() {
+        let sphere = MeshData::icosphere(1.0, 3);
+        let simplified = sphere.simplify(sphere.indices.len() / 3 / 4);
+        assert!(simplified.indices.len() / 3 <= sphere.indices.len() / 3 / 4);
+        for vertex in simplified.vertices.iter() {
+            let distance = vertex.position().magnitude();
+            assert!((distance - 1.0).abs() < 0.1, "{}", distance);
+        }
+        // Nothing is turned inside out.
+        for triangle in simplified.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| simplified.vertices[triangle[i] as usize].position());
+            assert!((b - a).cross(c - a).dot(a + b + c) > 0.0);
+        }
+    }
+
+    #[test]
+    fn lod_chain() {
+        let chain = MeshData::uv_sphere(1.0, 32, 16).lod_chain(4, 0.5);
+        assert_eq!(chain.len(), 4);
+        for pair in chain.windows(2) {
+            assert!(pair[1].indices.len() < pair[0].indices.len());
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use gl::Gl;
+use std::{ffi::CString, rc::Rc};
+
+use super::{Camera, Error, Model, PositionVertex, Program, Shader, Texture};
+
+const CORNERS: [[f32; 3]; 8] = [
+    [-1.0, -1.0, -1.0],
+    [1.0, -1.0, -1.0],
+    [1.0, 1.0, -1.0],
+    [-1.0, 1.0, -1.0],
+    [-1.0, -1.0, 1.0],
+    [1.0, -1.0, 1.0],
+    [1.0, 1.0, 1.0],
+    [-1.0, 1.0, 1.0],
+];
+
+const INDICES: [u32; 36] = [
+    0, 1, 2, 2, 3, 0, // -Z
+    4, 6, 5, 6, 4, 7, // +Z
+    0, 3, 7, 7, 4, 0, // -X
+    1, 5, 6, 6, 2, 1, // +X
+    3, 2, 6, 6, 7, 3, // +Y
+    0, 4, 5, 5, 1, 0, // -Y
+];
+
+/// A cubemap drawn around the camera, turning with it but never getting any closer.
+pub struct Skybox {
+    gl: Gl,
+    texture: Rc<Texture>,
+    program: Program,
+    cube: Model,
+}
+
+impl Skybox {
+    pub fn new(gl: Gl, texture: Rc<Texture>) -> Result<Self, Error> {
+        assert!(texture.is_cubemap(), "A skybox needs a cubemap texture");
+        let source = |source: &str| CString::new(source).unwrap();
+        let program = Program::new(
+            gl.clone(),
+            &[
+                Shader::from_vert_source(
+                    gl.clone(),
+                    &source(include_str!("../resources/skybox.vert")),
+                )?,
+                Shader::from_frag_source(
+                    gl.clone(),
+                    &source(include_str!("../resources/skybox.frag")),
+                )?,
+            ],
+        )?;
+        let corners: Vec<_> = CORNERS
+            .iter()
+            .map(|&position| PositionVertex { position })
+            .collect();
+        let cube = Model::from_vertices(gl.clone(), &corners, Some(&INDICES), gl::TRIANGLES);
+        Ok(Self {
+            gl,
+            texture,
+            program,
+            cube,
+        })
+    }
+
+    pub fn texture(&self) -> &Rc<Texture> {
+        &self.texture
+    }
+
+    /// Fills whatever the scene left at the far plane, so it goes after the scene
+    /// and only costs the pixels that are still empty. Uses texture unit 0.
+    pub fn draw(&self, camera: &Camera) {
+        self.program.set_used();
+        self.program
+            .set_matrix4("rotation", &camera.rotation_matrix())
+            .unwrap();
+        self.program.set_int("Sky", 0).unwrap();
+        self.texture.bind_n(0);
+        unsafe {
+            self.gl.DepthFunc(gl::LEQUAL);
+            self.gl.DepthMask(gl::FALSE);
+        }
+        self.cube.draw(self.gl.clone());
+        unsafe {
+            self.gl.DepthMask(gl::TRUE);
+            self.gl.DepthFunc(gl::LESS);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use cgmath::{InnerSpace, Vector3};
+use std::{convert::TryInto, path::Path};
+
+use super::{
+    models::{MeshData, VertexData},
+    parse, Error,
+};
+
+const HEADER_SIZE: usize = 84;
+const TRIANGLE_SIZE: usize = 50;
+
+/// Binary files are recognized by their size, as plenty of exporters start them with `solid` too.
+pub(super) fn parse_stl(file_name: &Path, bytes: &[u8]) -> Result<MeshData, Error> {
+    if bytes.len() >= HEADER_SIZE {
+        let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+        if bytes.len() == HEADER_SIZE + count * TRIANGLE_SIZE {
+            return Ok(parse_binary(bytes, count));
+        }
+    }
+    if !bytes.starts_with(b"solid") {
+        return Err(Error::invalid_data(
+            file_name,
+            "Neither an ASCII nor a binary STL file".to_string(),
+        ));
+    }
+    let text = String::from_utf8_lossy(bytes);
+    parse_ascii(file_name, &text)
+}
+
+fn parse_binary(bytes: &[u8], count: usize) -> MeshData {
+    let float = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let vector = |offset: usize| Vector3::new(float(offset), float(offset + 4), float(offset + 8));
+
+    let mut data = MeshData::default();
+    for i in 0..count {
+        let offset = HEADER_SIZE + i * TRIANGLE_SIZE;
+        let corners = [
+            vector(offset + 12),
+            vector(offset + 24),
+            vector(offset + 36),
+        ];
+        push_triangle(&mut data, vector(offset), corners);
+    }
+    data
+}
+
+fn parse_ascii(file_name: &Path, text: &str) -> Result<MeshData, Error> {
+    let mut data = MeshData::default();
+    let mut normal = Vector3::new(0.0, 0.0, 0.0);
+    let mut corners = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let error = |message: String| Error::parse(file_name, number + 1, message);
+        let split: Vec<&str> = line.split_whitespace().collect();
+        match split.first() {
+            Some(&"facet") => {
+                if split.len() < 5 {
+                    return Err(error("Not enough coordinates for a normal".to_string()));
+                }
+                normal = parse_vector(&split[2..]).map_err(error)?;
+                corners.clear();
+            }
+            Some(&"vertex") => {
+                if split.len() < 4 {
+                    return Err(error("Not enough coordinates for a vertex".to_string()));
+                }
+                corners.push(parse_vector(&split[1..]).map_err(error)?);
+            }
+            Some(&"endfacet") => {
+                if corners.len() != 3 {
+                    return Err(error(format!(
+                        "A facet has {} vertices instead of 3",
+                        corners.len()
+                    )));
+                }
+                push_triangle(&mut data, normal, [corners[0], corners[1], corners[2]]);
+            }
+            _ => continue,
+        }
+    }
+    Ok(data)
+}
+
+fn parse_vector(split: &[&str]) -> Result<Vector3<f32>, String> {
+    Ok(Vector3::new(
+        parse::<f32>(split[0])?,
+        parse::<f32>(split[1])?,
+        parse::<f32>(split[2])?,
+    ))
+}
+
+/// Plenty of exporters leave the stored normal zeroed, it is recalculated from the corners then.
+fn push_triangle(data: &mut MeshData, normal: Vector3<f32>, corners: [Vector3<f32>; 3]) {
+    let mut normal = normal;
+    if normal.magnitude2() == 0.0 {
+        normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+        if normal.magnitude2() != 0.0 {
+            normal = normal.normalize();
+        }
+    }
+    for corner in corners.iter() {
+        data.indices.push(data.vertices.len() as u32);
+        data.vertices.push(VertexData::new(
+            (*corner).into(),
+            [0.0, 0.0],
+            [1.0, 1.0, 1.0],
+            normal.into(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii() {
+        let source = "solid test
+facet normal 0 0 0
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid test";
+        let data = parse_stl(Path::new("test.stl"), source.as_bytes()).unwrap();
+        assert_eq!(data.indices, vec![0, 1, 2]);
+        assert_eq!(data.vertices[1].position(), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(data.vertices[1].normal(), Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn binary() {
+        // Starts with `solid` on purpose, the size has to win.
+        let mut bytes = b"solid".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        for value in [
+            0.0f32, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0,
+        ]
+        .iter()
+        {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&[0, 0]);
+
+        let data = parse_stl(Path::new("test.stl"), &bytes).unwrap();
+        assert_eq!(data.vertices.len(), 3);
+        assert_eq!(data.vertices[2].position(), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(data.vertices[2].normal(), Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn broken_facet() {
+        let source = "solid test\nfacet normal 0 0 1\nvertex 0 0 0\nvertex 1 0 0\nendfacet\n";
+        assert!(parse_stl(Path::new("test.stl"), source.as_bytes()).is_err());
+    }
+}
@@ -1,62 +1,638 @@
-use gl::types::GLint;
+use gl::types::{GLenum, GLint, GLsizei};
+use image::RgbaImage;
 
 use std::{ffi::c_void, path::Path};
 
+use super::{
+    compression::{level_size, BlockFormat},
+    objects::{self, ObjectKind},
+    Error, TextureOptions,
+};
+
+/// How the pixels of a texture are stored, both on the GPU and in the data given to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    R8,
+    Rg8,
+    Rgb8,
+    Rgba8,
+    R16F,
+    Rgba16F,
+    Rgba32F,
+    Depth24,
+    Depth32F,
+    /// Depth and stencil packed in 32 bits, 8 of them stencil.
+    Depth24Stencil8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::R8 => 1,
+            PixelFormat::Rg8 | PixelFormat::R16F => 2,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8
+            | PixelFormat::Depth24
+            | PixelFormat::Depth32F
+            | PixelFormat::Depth24Stencil8 => 4,
+            PixelFormat::Rgba16F => 8,
+            PixelFormat::Rgba32F => 16,
+        }
+    }
+
+    /// Whether the format is for depth attachments rather than colors.
+    pub fn is_depth(self) -> bool {
+        matches!(
+            self,
+            PixelFormat::Depth24 | PixelFormat::Depth32F | PixelFormat::Depth24Stencil8
+        )
+    }
+
+    pub(super) fn internal_format(self) -> GLenum {
+        match self {
+            PixelFormat::R8 => gl::R8,
+            PixelFormat::Rg8 => gl::RG8,
+            PixelFormat::Rgb8 => gl::RGB8,
+            PixelFormat::Rgba8 => gl::RGBA8,
+            PixelFormat::R16F => gl::R16F,
+            PixelFormat::Rgba16F => gl::RGBA16F,
+            PixelFormat::Rgba32F => gl::RGBA32F,
+            PixelFormat::Depth24 => gl::DEPTH_COMPONENT24,
+            PixelFormat::Depth32F => gl::DEPTH_COMPONENT32F,
+            PixelFormat::Depth24Stencil8 => gl::DEPTH24_STENCIL8,
+        }
+    }
+
+    fn format(self) -> GLenum {
+        match self {
+            PixelFormat::R8 | PixelFormat::R16F => gl::RED,
+            PixelFormat::Rg8 => gl::RG,
+            PixelFormat::Rgb8 => gl::RGB,
+            PixelFormat::Rgba8 | PixelFormat::Rgba16F | PixelFormat::Rgba32F => gl::RGBA,
+            PixelFormat::Depth24 | PixelFormat::Depth32F => gl::DEPTH_COMPONENT,
+            PixelFormat::Depth24Stencil8 => gl::DEPTH_STENCIL,
+        }
+    }
+
+    fn data_type(self) -> GLenum {
+        match self {
+            PixelFormat::R16F | PixelFormat::Rgba16F => gl::HALF_FLOAT,
+            PixelFormat::Rgba32F | PixelFormat::Depth32F => gl::FLOAT,
+            PixelFormat::Depth24 => gl::UNSIGNED_INT,
+            PixelFormat::Depth24Stencil8 => gl::UNSIGNED_INT_24_8,
+            _ => gl::UNSIGNED_BYTE,
+        }
+    }
+}
+
 pub struct Texture {
     gl: gl::Gl,
-    #[allow(dead_code)]
+    /// The pixels as they were uploaded, in `format`. Empty for textures made without pixels,
+    /// the blocks of the full size level for compressed textures.
     pub bytes: Vec<u8>,
     pub id: gl::types::GLuint,
+    /// `TEXTURE_2D`, `TEXTURE_2D_ARRAY` for textures made of layers or `TEXTURE_CUBE_MAP`.
+    target: GLenum,
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: PixelFormat,
+    options: TextureOptions,
+    has_mipmaps: bool,
+    /// Set when the GPU keeps the pixels compressed, `format` is what they are sampled as.
+    compression: Option<BlockFormat>,
 }
 
 impl Texture {
-    pub fn new(gl: gl::Gl, fname: &Path) -> Self {
+    /// Loads the image with the default, smooth options.
+    pub fn new(gl: gl::Gl, fname: &Path) -> Result<Self, Error> {
+        Self::with_options(gl, fname, TextureOptions::default())
+    }
+
+    pub fn with_options(gl: gl::Gl, fname: &Path, options: TextureOptions) -> Result<Self, Error> {
+        let image = image::open(fname)
+            .map_err(|source| Error::Image {
+                path: fname.to_path_buf(),
+                source,
+            })?
+            .into_rgba8();
+
+        Ok(Self::from_rgba8(gl, image, options))
+    }
+
+    /// Decodes an image file already in memory, e.g. from `include_bytes!`.
+    pub fn from_encoded(gl: gl::Gl, bytes: &[u8], options: TextureOptions) -> Result<Self, Error> {
+        let image = image::load_from_memory(bytes)
+            .map_err(|source| Error::ImageDecode { source })?
+            .into_rgba8();
+        Ok(Self::from_rgba8(gl, image, options))
+    }
+
+    pub(super) fn from_rgba8(gl: gl::Gl, image: RgbaImage, options: TextureOptions) -> Self {
+        let (width, height) = image.dimensions();
+        Self::from_pixels(
+            gl,
+            width,
+            height,
+            PixelFormat::Rgba8,
+            image.as_raw(),
+            options,
+        )
+    }
+
+    /// Uploads rows of `format` pixels as they are, starting from the top of the image
+    /// like the loaders do. `T` is whatever holds the components, e.g. `u8`, `f32`
+    /// or `u16` with the bits of half floats.
+    pub fn from_pixels<T: PixelData>(
+        gl: gl::Gl,
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+        pixels: &[T],
+        options: TextureOptions,
+    ) -> Self {
+        let bytes = as_bytes(pixels).to_vec();
+        assert_eq!(
+            bytes.len(),
+            width as usize * height as usize * format.bytes_per_pixel(),
+            "{}x{} {:?} pixels do not fit the data",
+            width,
+            height,
+            format
+        );
+        Self::upload(
+            gl,
+            gl::TEXTURE_2D,
+            (width, height, 1),
+            format,
+            bytes,
+            options,
+        )
+    }
+
+    /// A texture without pixels, to be drawn into or filled with `update_region`.
+    pub fn empty(
+        gl: gl::Gl,
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+        options: TextureOptions,
+    ) -> Self {
+        let size = (width, height, 1);
+        Self::upload(gl, gl::TEXTURE_2D, size, format, vec![], options)
+    }
+
+    /// An array texture with an image per layer, sampled in shaders with a `sampler2DArray`
+    /// and the layer as the third coordinate. Every image has to be the same size.
+    pub fn array_from_rgba8(gl: gl::Gl, images: &[RgbaImage], options: TextureOptions) -> Self {
+        assert!(
+            !images.is_empty(),
+            "An array texture needs at least one layer"
+        );
+        let sizes: Vec<_> = images.iter().map(RgbaImage::dimensions).collect();
+        if let Some(layer) = mismatched_layer(&sizes) {
+            panic!(
+                "Layer {} is {}x{}, the first layer is {}x{}",
+                layer, sizes[layer].0, sizes[layer].1, sizes[0].0, sizes[0].1
+            );
+        }
+        let (width, height) = sizes[0];
+        let bytes = images
+            .iter()
+            .flat_map(|image| image.as_raw())
+            .copied()
+            .collect();
+        let size = (width, height, images.len() as u32);
+        Self::upload(
+            gl,
+            gl::TEXTURE_2D_ARRAY,
+            size,
+            PixelFormat::Rgba8,
+            bytes,
+            options,
+        )
+    }
+
+    /// Loads an array texture with a layer per file, in the order of `files`.
+    pub fn array_from_files(
+        gl: gl::Gl,
+        files: &[&Path],
+        options: TextureOptions,
+    ) -> Result<Self, Error> {
+        let mut images = vec![];
+        for fname in files.iter() {
+            let image = image::open(fname)
+                .map_err(|source| Error::Image {
+                    path: fname.to_path_buf(),
+                    source,
+                })?
+                .into_rgba8();
+            images.push(image);
+        }
+        let sizes: Vec<_> = images.iter().map(RgbaImage::dimensions).collect();
+        if let Some(layer) = mismatched_layer(&sizes) {
+            return Err(Error::invalid_data(
+                files[layer],
+                format!(
+                    "the layer is {}x{}, the first layer is {}x{}",
+                    sizes[layer].0, sizes[layer].1, sizes[0].0, sizes[0].1
+                ),
+            ));
+        }
+        Ok(Self::array_from_rgba8(gl, &images, options))
+    }
+
+    /// Creates the texture out of `layers` images of `width` x `height` pixels one after another.
+    /// Cubemaps have a layer per face, in the order of the `TEXTURE_CUBE_MAP_*` targets.
+    pub(super) fn upload(
+        gl: gl::Gl,
+        target: GLenum,
+        (width, height, layers): (u32, u32, u32),
+        format: PixelFormat,
+        bytes: Vec<u8>,
+        options: TextureOptions,
+    ) -> Self {
+        let mut texture = 0;
+        unsafe {
+            gl.GenTextures(1, &mut texture);
+            gl.BindTexture(target, texture);
+        }
+        objects::created(ObjectKind::Texture);
+
+        // No pixels only allocates the storage, e.g. for render targets.
+        let pixels = |offset: usize| {
+            if bytes.is_empty() {
+                std::ptr::null()
+            } else {
+                bytes[offset..].as_ptr() as *const c_void
+            }
+        };
+        unsafe {
+            // Rows of odd sized pixels are not padded to 4 bytes.
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            if target == gl::TEXTURE_2D_ARRAY {
+                gl.TexImage3D(
+                    target,
+                    0,
+                    format.internal_format() as GLint,
+                    width as GLint,
+                    height as GLint,
+                    layers as GLint,
+                    0,
+                    format.format(),
+                    format.data_type(),
+                    pixels(0),
+                );
+            } else if target == gl::TEXTURE_CUBE_MAP {
+                let face_size = bytes.len() / layers as usize;
+                for face in 0..layers {
+                    gl.TexImage2D(
+                        gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                        0,
+                        format.internal_format() as GLint,
+                        width as GLint,
+                        height as GLint,
+                        0,
+                        format.format(),
+                        format.data_type(),
+                        pixels(face as usize * face_size),
+                    );
+                }
+            } else {
+                gl.TexImage2D(
+                    target,
+                    0,
+                    format.internal_format() as GLint,
+                    width as GLint,
+                    height as GLint,
+                    0,
+                    format.format(),
+                    format.data_type(),
+                    pixels(0),
+                );
+            }
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            if options.generate_mipmaps {
+                gl.GenerateMipmap(target);
+            }
+        }
+        options.apply_to_texture(&gl, target, options.generate_mipmaps);
+        unsafe {
+            gl.BindTexture(target, 0);
+        }
+        Self {
+            gl,
+            id: texture,
+            bytes,
+            target,
+            width,
+            height,
+            layers,
+            format,
+            options,
+            has_mipmaps: options.generate_mipmaps,
+            compression: None,
+        }
+    }
+
+    /// Creates a texture out of a full size image and its mip levels, each half the size of
+    /// the one before. Levels are either `Rgba8` pixels or blocks of `compression`.
+    /// A single uncompressed level gets generated mipmaps like any other texture.
+    pub(super) fn from_levels(
+        gl: gl::Gl,
+        width: u32,
+        height: u32,
+        mut levels: Vec<Vec<u8>>,
+        compression: Option<BlockFormat>,
+        options: TextureOptions,
+    ) -> Self {
+        let format = PixelFormat::Rgba8;
         let mut texture = 0;
         unsafe {
             gl.GenTextures(1, &mut texture);
             gl.BindTexture(gl::TEXTURE_2D, texture);
         }
-        let image = image::open(fname).unwrap().into_rgba8();
+        objects::created(ObjectKind::Texture);
 
-        let (w, h) = (image.width() as GLint, image.height() as GLint);
-        let bytes = image.into_vec();
+        let generate = levels.len() == 1 && compression.is_none() && options.generate_mipmaps;
+        unsafe {
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            for (level, data) in levels.iter().enumerate() {
+                let (level_width, level_height) = level_size(width, height, level);
+                match compression {
+                    Some(block) => gl.CompressedTexImage2D(
+                        gl::TEXTURE_2D,
+                        level as GLint,
+                        block.internal_format(),
+                        level_width as GLint,
+                        level_height as GLint,
+                        0,
+                        data.len() as GLsizei,
+                        data.as_ptr() as *const c_void,
+                    ),
+                    None => gl.TexImage2D(
+                        gl::TEXTURE_2D,
+                        level as GLint,
+                        format.internal_format() as GLint,
+                        level_width as GLint,
+                        level_height as GLint,
+                        0,
+                        format.format(),
+                        format.data_type(),
+                        data.as_ptr() as *const c_void,
+                    ),
+                }
+            }
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            if generate {
+                gl.GenerateMipmap(gl::TEXTURE_2D);
+            } else if levels.len() > 1 {
+                // Files may stop before the 1x1 level, the texture is complete without the rest.
+                let max_level = levels.len() as GLint - 1;
+                gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, max_level);
+            }
+        }
+        let has_mipmaps = generate || levels.len() > 1;
+        options.apply_to_texture(&gl, gl::TEXTURE_2D, has_mipmaps);
         unsafe {
-            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl.TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                gl::RGBA8 as GLint,
-                w,
-                h,
-                0,
-                gl::RGBA,
-                gl::UNSIGNED_BYTE,
-                bytes.as_ptr() as *mut c_void,
-            );
-            gl.GenerateMipmap(gl::TEXTURE_2D);
             gl.BindTexture(gl::TEXTURE_2D, 0);
         }
         Self {
             gl,
             id: texture,
-            bytes,
+            bytes: levels.swap_remove(0),
+            target: gl::TEXTURE_2D,
+            width,
+            height,
+            layers: 1,
+            format,
+            options,
+            has_mipmaps,
+            compression,
         }
     }
 
-    pub fn bind(&self) {
+    /// Overwrites the `width` x `height` rectangle whose top left corner is at `x`, `y`.
+    /// `pixels` have to be in the format of the texture, mipmaps are regenerated.
+    /// Array textures, cubemaps and compressed textures are not supported.
+    pub fn update_region<T: PixelData>(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        pixels: &[T],
+    ) {
+        assert!(
+            self.target == gl::TEXTURE_2D && self.compression.is_none(),
+            "Only plain uncompressed 2D textures can be updated"
+        );
+        assert!(
+            region_fits((x, y, width, height), (self.width, self.height)),
+            "The region {}x{} at {}, {} is outside of the {}x{} texture",
+            width,
+            height,
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        let pixels = as_bytes(pixels);
+        let pixel_size = self.format.bytes_per_pixel();
+        assert_eq!(
+            pixels.len(),
+            width as usize * height as usize * pixel_size,
+            "{}x{} {:?} pixels do not fit the data",
+            width,
+            height,
+            self.format
+        );
+
+        unsafe {
+            self.gl.BindTexture(self.target, self.id);
+            self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            self.gl.TexSubImage2D(
+                self.target,
+                0,
+                x as GLint,
+                y as GLint,
+                width as GLint,
+                height as GLint,
+                self.format.format(),
+                self.format.data_type(),
+                pixels.as_ptr() as *const c_void,
+            );
+            self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            if self.has_mipmaps {
+                self.gl.GenerateMipmap(self.target);
+            }
+            self.gl.BindTexture(self.target, 0);
+        }
+        if !self.bytes.is_empty() {
+            copy_region(
+                &mut self.bytes,
+                self.width as usize * pixel_size,
+                (x as usize * pixel_size, y as usize),
+                width as usize * pixel_size,
+                pixels,
+            );
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// 6 for cubemaps, 1 for anything but array textures.
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.target == gl::TEXTURE_2D_ARRAY
+    }
+
+    pub fn is_cubemap(&self) -> bool {
+        self.target == gl::TEXTURE_CUBE_MAP
+    }
+
+    pub fn compression(&self) -> Option<BlockFormat> {
+        self.compression
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    pub fn options(&self) -> &TextureOptions {
+        &self.options
+    }
+
+    /// Changes how the texture is sampled, generating the mipmaps if they are asked for
+    /// and missing, except for compressed textures. Mipmaps that are already there are kept.
+    pub fn set_options(&mut self, options: TextureOptions) {
         unsafe {
-            self.gl.BindTexture(gl::TEXTURE_2D, self.id);
-            self.gl.ActiveTexture(gl::TEXTURE0);
+            self.gl.BindTexture(self.target, self.id);
+            if options.generate_mipmaps && !self.has_mipmaps && self.compression.is_none() {
+                self.gl.GenerateMipmap(self.target);
+                self.has_mipmaps = true;
+            }
         }
+        options.apply_to_texture(&self.gl, self.target, self.has_mipmaps);
+        unsafe {
+            self.gl.BindTexture(self.target, 0);
+        }
+        self.options = options;
     }
 
+    pub fn bind(&self) {
+        self.bind_n(0);
+    }
+
+    /// Binds the texture to unit `n`, which stays active afterwards.
     pub fn bind_n(&self, n: u32) {
         unsafe {
-            self.gl.BindTexture(gl::TEXTURE_2D, self.id);
             self.gl.ActiveTexture(gl::TEXTURE0 + n);
+            self.gl.BindTexture(self.target, self.id);
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteTextures(1, &self.id);
         }
+        objects::deleted(ObjectKind::Texture);
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Plain numbers pixels are made of. None of them has padding, so every byte of a slice
+/// of them can be read, which is why the trait cannot be implemented elsewhere.
+pub trait PixelData: Copy + sealed::Sealed {}
+
+macro_rules! pixel_data {
+    ($($type:ty),*) => {
+        $(
+            impl sealed::Sealed for $type {}
+            impl PixelData for $type {}
+        )*
+    };
+}
+
+pixel_data!(u8, i8, u16, i16, u32, i32, f32);
+
+impl<T: PixelData, const N: usize> sealed::Sealed for [T; N] {}
+impl<T: PixelData, const N: usize> PixelData for [T; N] {}
+
+fn as_bytes<T: PixelData>(values: &[T]) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(values.as_ptr() as *const u8, std::mem::size_of_val(values))
+    }
+}
+
+/// Whether the `(x, y, width, height)` region lies within a texture of `size`.
+fn region_fits((x, y, width, height): (u32, u32, u32, u32), size: (u32, u32)) -> bool {
+    x.checked_add(width).is_some_and(|right| right <= size.0)
+        && y.checked_add(height).is_some_and(|bottom| bottom <= size.1)
+}
+
+/// The first image whose size differs from the first one.
+fn mismatched_layer(sizes: &[(u32, u32)]) -> Option<usize> {
+    sizes.iter().position(|&size| size != sizes[0])
+}
+
+/// Copies `region` rows of `row_size` bytes into `target`, whose rows are `stride` long,
+/// starting at byte `x` of row `y`.
+fn copy_region(
+    target: &mut [u8],
+    stride: usize,
+    (x, y): (usize, usize),
+    row_size: usize,
+    region: &[u8],
+) {
+    for (i, row) in region.chunks(row_size).enumerate() {
+        let start = (y + i) * stride + x;
+        target[start..start + row_size].copy_from_slice(row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions() {
+        // A 3x2 RGB8 image, updating its second column.
+        let size = PixelFormat::Rgb8.bytes_per_pixel();
+        let mut bytes = vec![0u8; 3 * 2 * size];
+        copy_region(&mut bytes, 3 * size, (size, 0), size, &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            bytes,
+            vec![0, 0, 0, 1, 2, 3, 0, 0, 0, 0, 0, 0, 4, 5, 6, 0, 0, 0]
+        );
+
+        assert_eq!(as_bytes(&[1.0f32, 2.0]).len(), 8);
+        assert_eq!(as_bytes(&[[1u16, 2, 3], [4, 5, 6]]).len(), 12);
+        assert!(region_fits((1, 0, 2, 2), (3, 2)));
+        assert!(!region_fits((2, 0, 2, 2), (3, 2)));
+        assert!(!region_fits((u32::MAX, 0, 2, 1), (3, 2)));
+        assert_eq!(PixelFormat::Rgba32F.bytes_per_pixel(), 4 * 4);
+        assert_eq!(PixelFormat::Rgba16F.data_type(), gl::HALF_FLOAT);
+    }
+
+    #[test]
+    fn layer_sizes() {
+        assert_eq!(mismatched_layer(&[(16, 16)]), None);
+        assert_eq!(mismatched_layer(&[(16, 16), (16, 16), (16, 16)]), None);
+        assert_eq!(mismatched_layer(&[(16, 16), (16, 16), (16, 8)]), Some(2));
     }
 }
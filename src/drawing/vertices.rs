@@ -0,0 +1,340 @@
+use cgmath::Vector3;
+use gl::{
+    types::{GLenum, GLint, GLsizeiptr, GLuint, GLvoid},
+    Gl,
+};
+
+use super::{
+    models::VertexData,
+    objects::{Buffer, VertexArray},
+};
+
+/// The attribute picking the layer of an array texture, see `LayeredVertex`.
+pub const LAYER_LOCATION: GLuint = 15;
+
+/// Where one attribute lives inside an interleaved vertex.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VertexAttribute {
+    pub location: GLuint,
+    pub components: GLint,
+    pub kind: GLenum,
+    /// Integer attributes reach the shader as `int`/`uint` instead of being converted to floats.
+    pub integer: bool,
+    pub offset: usize,
+}
+
+impl VertexAttribute {
+    pub const fn floats(location: GLuint, components: GLint, offset: usize) -> Self {
+        Self {
+            location,
+            components,
+            kind: gl::FLOAT,
+            integer: false,
+            offset,
+        }
+    }
+
+    pub const fn of<T: AttributeType>(location: GLuint, offset: usize) -> Self {
+        Self {
+            location,
+            components: T::COMPONENTS,
+            kind: T::KIND,
+            integer: T::INTEGER,
+            offset,
+        }
+    }
+}
+
+/// A vertex type that can be uploaded as is. The layout is usually written by [`vertex_format!`].
+pub trait Vertex: Clone {
+    fn attributes() -> Vec<VertexAttribute>;
+}
+
+/// A scalar that can make up an attribute.
+pub trait Component {
+    const KIND: GLenum;
+    const INTEGER: bool;
+}
+
+impl Component for f32 {
+    const KIND: GLenum = gl::FLOAT;
+    const INTEGER: bool = false;
+}
+
+macro_rules! integer_component {
+    ($($ty:ty => $kind:expr),*) => {
+        $(
+            impl Component for $ty {
+                const KIND: GLenum = $kind;
+                const INTEGER: bool = true;
+            }
+        )*
+    };
+}
+
+integer_component!(
+    u8 => gl::UNSIGNED_BYTE,
+    u16 => gl::UNSIGNED_SHORT,
+    u32 => gl::UNSIGNED_INT,
+    i8 => gl::BYTE,
+    i16 => gl::SHORT,
+    i32 => gl::INT
+);
+
+/// The type of a whole attribute: a single component or an array of up to four of them.
+pub trait AttributeType {
+    const COMPONENTS: GLint;
+    const KIND: GLenum;
+    const INTEGER: bool;
+}
+
+impl<T: Component> AttributeType for T {
+    const COMPONENTS: GLint = 1;
+    const KIND: GLenum = T::KIND;
+    const INTEGER: bool = T::INTEGER;
+}
+
+impl<T: Component, const N: usize> AttributeType for [T; N] {
+    const COMPONENTS: GLint = N as GLint;
+    const KIND: GLenum = T::KIND;
+    const INTEGER: bool = T::INTEGER;
+}
+
+/// Declares a `#[repr(C)]` vertex struct together with its [`Vertex`] implementation.
+///
+/// ```ignore
+/// vertex_format! {
+///     pub struct ShadowVertex {
+///         0 => pub position: [f32; 3],
+///     }
+/// }
+/// ```
+macro_rules! vertex_format {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($location:literal => $field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        impl $crate::drawing::Vertex for $name {
+            fn attributes() -> Vec<$crate::drawing::VertexAttribute> {
+                vec![
+                    $($crate::drawing::VertexAttribute::of::<$ty>(
+                        $location,
+                        std::mem::offset_of!($name, $field),
+                    )),*
+                ]
+            }
+        }
+    };
+}
+
+pub(crate) use vertex_format;
+
+vertex_format! {
+    /// Positions only, enough for depth and shadow passes.
+    pub struct PositionVertex {
+        0 => pub position: [f32; 3],
+    }
+}
+
+vertex_format! {
+    /// Everything normal mapping needs. Locations match `VertexData` where the attributes overlap.
+    pub struct TangentVertex {
+        0 => pub position: [f32; 3],
+        2 => pub texture_coordinates: [f32; 2],
+        3 => pub normal: [f32; 3],
+        4 => pub tangent: [f32; 4],
+    }
+}
+
+vertex_format! {
+    /// A vertex that picks its own layer of an array texture, e.g. for terrain where the
+    /// faces of one mesh show different blocks. Locations match `VertexData` otherwise.
+    pub struct LayeredVertex {
+        0 => pub position: [f32; 3],
+        1 => pub color: [f32; 3],
+        2 => pub texture_coordinates: [f32; 2],
+        3 => pub normal: [f32; 3],
+        15 => pub layer: f32,
+    }
+}
+
+impl LayeredVertex {
+    pub fn new(vertex: &VertexData, layer: u32) -> Self {
+        let (u, v) = vertex.texture_coordinates();
+        Self {
+            position: vertex.position().into(),
+            color: vertex.color().into(),
+            texture_coordinates: [u, v],
+            normal: vertex.normal().into(),
+            layer: layer as f32,
+        }
+    }
+}
+
+/// Sets the layer used by meshes whose vertices do not have one, until it is set again.
+/// Lets every instance of a mesh show a different layer.
+pub fn set_layer(gl: &Gl, layer: u32) {
+    unsafe {
+        gl.VertexAttrib1f(LAYER_LOCATION, layer as f32);
+    }
+}
+
+/// Uploads `vertices` into a single buffer and describes every attribute of it in a new VAO.
+/// Returns the VAO and the buffer.
+pub(super) fn make_vao<V: Vertex>(gl: Gl, vertices: &[V], usage: GLenum) -> (VertexArray, Buffer) {
+    let stride = std::mem::size_of::<V>();
+    let vao = VertexArray::new(gl.clone());
+    let vbo = Buffer::new(gl.clone());
+    vao.bind();
+    vbo.bind(gl::ARRAY_BUFFER);
+    unsafe {
+        gl.BufferData(
+            gl::ARRAY_BUFFER,
+            std::mem::size_of_val(vertices) as GLsizeiptr,
+            vertices.as_ptr() as *const GLvoid,
+            usage,
+        );
+
+        for attribute in V::attributes().iter() {
+            gl.EnableVertexAttribArray(attribute.location);
+            if attribute.integer {
+                gl.VertexAttribIPointer(
+                    attribute.location,
+                    attribute.components,
+                    attribute.kind,
+                    stride as GLint,
+                    attribute.offset as *const GLvoid,
+                );
+            } else {
+                gl.VertexAttribPointer(
+                    attribute.location,
+                    attribute.components,
+                    attribute.kind,
+                    gl::FALSE,
+                    stride as GLint,
+                    attribute.offset as *const GLvoid,
+                );
+            }
+        }
+
+        gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+        gl.BindVertexArray(0);
+    }
+    (vao, vbo)
+}
+
+/// Reads the positions back out of `vertices`, the shaders expect them at location 0.
+/// Vertex types without float positions there give no points.
+pub(super) fn positions<V: Vertex>(vertices: &[V]) -> Vec<Vector3<f32>> {
+    let attribute = V::attributes()
+        .into_iter()
+        .find(|a| a.location == 0 && a.kind == gl::FLOAT && !a.integer);
+    let attribute = match attribute {
+        Some(attribute) => attribute,
+        None => return vec![],
+    };
+    let components = (attribute.components.clamp(1, 3)) as usize;
+    // `attributes` comes from a safe trait, so the reads below cannot trust it blindly.
+    assert!(
+        attribute.offset + components * std::mem::size_of::<f32>() <= std::mem::size_of::<V>(),
+        "The position attribute of {} lies outside of it",
+        std::any::type_name::<V>()
+    );
+    vertices
+        .iter()
+        .map(|vertex| {
+            let mut position = [0.0f32; 3];
+            let base = vertex as *const V as *const u8;
+            for (i, component) in position.iter_mut().take(components).enumerate() {
+                // Only the attribute's own bytes are read, which the assert keeps inside
+                // the vertex.
+                *component = unsafe {
+                    std::ptr::read_unaligned(
+                        base.add(attribute.offset + i * std::mem::size_of::<f32>()) as *const f32,
+                    )
+                };
+            }
+            Vector3::from(position)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declared_layout() {
+        vertex_format! {
+            struct SkinnedVertex {
+                0 => position: [f32; 3],
+                5 => joints: [u8; 4],
+                6 => weights: [f32; 4],
+            }
+        }
+
+        assert_eq!(
+            SkinnedVertex::attributes(),
+            vec![
+                VertexAttribute::floats(0, 3, 0),
+                VertexAttribute {
+                    location: 5,
+                    components: 4,
+                    kind: gl::UNSIGNED_BYTE,
+                    integer: true,
+                    offset: 12,
+                },
+                VertexAttribute::floats(6, 4, 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_positions() {
+        vertex_format! {
+            struct FlatVertex {
+                1 => color: [u8; 4],
+                0 => position: [f32; 2],
+            }
+        }
+
+        let vertices = [
+            FlatVertex {
+                color: [0; 4],
+                position: [1.0, 2.0],
+            },
+            FlatVertex {
+                color: [255; 4],
+                position: [-3.0, 4.0],
+            },
+        ];
+        assert_eq!(
+            positions(&vertices),
+            vec![Vector3::new(1.0, 2.0, 0.0), Vector3::new(-3.0, 4.0, 0.0)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "lies outside of it")]
+    fn position_past_the_vertex() {
+        #[derive(Clone)]
+        struct ShortVertex;
+
+        impl Vertex for ShortVertex {
+            fn attributes() -> Vec<VertexAttribute> {
+                vec![VertexAttribute::floats(0, 3, 0)]
+            }
+        }
+
+        positions(&[ShortVertex]);
+    }
+}
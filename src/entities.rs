@@ -1,23 +1,106 @@
-use std::rc::Rc;
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
-use super::drawing::{Model, Texture};
+use super::drawing::{Animator, Camera, Lods, Mesh, Model, MorphAnimator, Texture};
 use cgmath::{Matrix, Matrix4, SquareMatrix};
 
 pub struct Entity {
     pub model: Rc<Model>,
-    pub texture: Rc<Texture>,
+    pub texture: Option<Rc<Texture>>,
     pub matrix: Matrix4<f32>,
+    /// Names of the model parts that are not drawn for this entity.
+    pub hidden_meshes: HashSet<String>,
+    /// Per-part textures, taking precedence over both materials and `texture`.
+    pub mesh_textures: HashMap<String, Rc<Texture>>,
+    /// Drawn instead of `model` when set, picking the level from the camera.
+    pub lods: Option<Rc<Lods>>,
+    /// Poses the joints of a skinned model.
+    pub animator: Option<Animator>,
+    /// Blends the morph targets of the model, which stays in its base shape without one.
+    pub morph: Option<MorphAnimator>,
+    /// The layer shown when the texture is an array texture and the vertices have none.
+    pub layer: u32,
 }
 
 impl Entity {
     pub fn new(model: Rc<Model>, texture: Rc<Texture>, matrix: Matrix4<f32>) -> Self {
         Self {
             model,
-            texture,
+            texture: Some(texture),
             matrix,
+            hidden_meshes: HashSet::new(),
+            mesh_textures: HashMap::new(),
+            lods: None,
+            animator: None,
+            morph: None,
+            layer: 0,
         }
     }
 
+    /// An entity whose model brings its own textures through materials.
+    pub fn with_materials(model: Rc<Model>, matrix: Matrix4<f32>) -> Self {
+        Self {
+            model,
+            texture: None,
+            matrix,
+            hidden_meshes: HashSet::new(),
+            mesh_textures: HashMap::new(),
+            lods: None,
+            animator: None,
+            morph: None,
+            layer: 0,
+        }
+    }
+
+    pub fn with_lods(mut self, lods: Rc<Lods>) -> Self {
+        self.lods = Some(lods);
+        self
+    }
+
+    pub fn with_animator(mut self, animator: Animator) -> Self {
+        self.animator = Some(animator);
+        self
+    }
+
+    pub fn with_morph(mut self, morph: MorphAnimator) -> Self {
+        self.morph = Some(morph);
+        self
+    }
+
+    pub fn with_layer(mut self, layer: u32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    /// The model to draw as seen from `camera`.
+    pub fn model_for(&self, camera: &Camera) -> &Rc<Model> {
+        match &self.lods {
+            Some(lods) => lods.select(&self.matrix, camera),
+            None => &self.model,
+        }
+    }
+
+    /// The meshes of the model that should be drawn, paired with the texture chosen for each.
+    pub fn visible_meshes<'a>(
+        &'a self,
+        camera: &'a Camera,
+    ) -> impl Iterator<Item = (&'a Mesh, Option<&'a Rc<Texture>>)> {
+        self.model_for(camera)
+            .meshes()
+            .iter()
+            .filter(move |mesh| !self.hidden_meshes.contains(mesh.name()))
+            .map(move |mesh| {
+                let texture = self
+                    .mesh_textures
+                    .get(mesh.name())
+                    .or_else(|| mesh.material().and_then(|m| m.diffuse_texture()))
+                    .or(self.texture.as_ref());
+                (mesh, texture)
+            })
+    }
+
     pub fn matrix(&self) -> Matrix4<f32> {
         self.matrix
     }
@@ -0,0 +1,6 @@
+extern crate cgmath;
+extern crate gl;
+extern crate image;
+
+pub mod drawing;
+pub mod entities;
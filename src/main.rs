@@ -1,18 +1,16 @@
 extern crate cgmath;
 extern crate gl;
-extern crate image;
+extern crate opengl_test;
 extern crate sdl2;
 
-mod drawing;
-mod entities;
 mod world;
 
-use drawing::{Program, Shader};
+use opengl_test::drawing::{self, Error, Program, Shader, Skybox, Texture, TextureOptions};
 use world::World;
 
-use cgmath::Rad;
+use cgmath::{Matrix4, Rad};
 use sdl2::keyboard::Keycode;
-use std::ffi::CString;
+use std::{ffi::CString, path::Path, rc::Rc};
 
 fn main() {
     let w = 1000.0f32;
@@ -47,17 +45,32 @@ fn main() {
     // )
     // .unwrap();
 
-    let programs = [load_program(gl.clone(), "guro"), load_program(gl.clone(), "phong")];
+    let programs = [
+        load_program(gl.clone(), "guro").unwrap(),
+        load_program(gl.clone(), "phong").unwrap(),
+    ];
     let mut current_program = 0;
     //Program::new(gl.clone(), &[vert_shader_phong, frag_shader]).unwrap();
     //shader_program.set_used();
 
     let mut world = World::new(gl.clone());
+    // glTF scenes named on the command line are added where their own nodes place them.
+    for file_name in std::env::args().skip(1) {
+        world.add_gltf(Path::new(&file_name), Matrix4::from_scale(1.0)).unwrap();
+    }
+    let sky = Texture::cubemap_from_equirectangular(
+        gl.clone(),
+        Path::new("src/resources/sky.png"),
+        TextureOptions::default(),
+    )
+    .unwrap();
+    let skybox = Skybox::new(gl.clone(), Rc::new(sky)).unwrap();
 
     unsafe {
         gl.Viewport(0, 0, w as gl::types::GLint, h as gl::types::GLint);
         gl.ClearColor(0.3, 0.3, 0.5, 1.0);
         gl.Enable(gl::DEPTH_TEST);
+        gl.Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
     }
 
     let mut camera = drawing::Camera::new((0.0, 0.0, 0.0).into(), Rad(0.0), Rad(0.0), w / h);
@@ -157,23 +170,42 @@ fn main() {
                 _ => {}
             }
         }
-        world.tick(&camera, &programs[current_program], delta_time);
+        if let Err(error) = world.tick(&camera, &programs[current_program], delta_time) {
+            eprintln!("{}", error);
+            break 'main;
+        }
+        skybox.draw(&camera);
         window.gl_swap_window();
     }
+
+    // Everything owning GL objects goes before the context does.
+    drop(world);
+    drop(skybox);
+    drop(programs);
+    drawing::report_leaks();
 }
 
-fn load_program(gl: gl::Gl, name: &str) -> Program {
+fn load_program(gl: gl::Gl, name: &str) -> Result<Program, Error> {
     let vert_shader_phong = Shader::from_vert_source(
         gl.clone(),
-        &CString::new(std::fs::read_to_string(format!("src/resources/{}.vert", name)).unwrap()).unwrap(),
-    )
-    .unwrap();
+        &read_shader_source(format!("src/resources/{}.vert", name))?,
+    )?;
 
     let frag_shader = Shader::from_frag_source(
         gl.clone(),
-        &CString::new(std::fs::read_to_string(format!("src/resources/{}.frag", name)).unwrap()).unwrap(),
-    )
-    .unwrap();
+        &read_shader_source(format!("src/resources/{}.frag", name))?,
+    )?;
 
-    Program::new(gl.clone(), &[vert_shader_phong, frag_shader]).unwrap()
-}
\ No newline at end of file
+    Program::new(gl.clone(), &[vert_shader_phong, frag_shader])
+}
+
+fn read_shader_source(path: String) -> Result<CString, Error> {
+    let path = std::path::PathBuf::from(path);
+    match std::fs::read_to_string(&path) {
+        Ok(source) => CString::new(source).map_err(|e| Error::InvalidData {
+            message: format!("NUL byte at {} in the shader", e.nul_position()),
+            path,
+        }),
+        Err(source) => Err(Error::Io { path, source }),
+    }
+}
@@ -3,17 +3,27 @@
 in VS_OUTPUT {
     vec3 Color;
     vec2 TextureCoords;
-    float Brightness;
+    flat float Layer;
+    vec3 Brightness;
 } IN;
 
 out vec4 Color;
 
 uniform sampler2D Texture;
 uniform sampler2D Texture_2;
+uniform sampler2DArray Texture_Array;
+uniform bool layered;
 
 uniform float color_coeff;
 uniform float texture_coeff;
 
+vec4 base_texture() {
+    if (layered) {
+        return texture(Texture_Array, vec3(IN.TextureCoords, IN.Layer));
+    }
+    return texture(Texture, IN.TextureCoords);
+}
+
 void main() {
-    Color = (vec4(IN.Color, 1.0f) * color_coeff + mix(texture(Texture, IN.TextureCoords), texture(Texture_2, IN.TextureCoords), texture_coeff) * (1.0 - color_coeff)) * IN.Brightness;
+    Color = (vec4(IN.Color, 1.0f) * color_coeff + mix(base_texture(), texture(Texture_2, IN.TextureCoords), texture_coeff) * (1.0 - color_coeff)) * vec4(IN.Brightness, 1.0);
 }
\ No newline at end of file
@@ -4,17 +4,49 @@ layout (location = 0) in vec3 Position;
 layout (location = 1) in vec3 Color;
 layout (location = 2) in vec2 TextureCoords;
 layout (location = 3) in vec3 Normal;
+layout (location = 5) in uvec4 Joints;
+layout (location = 6) in vec4 Weights;
+
+#define MAX_MORPH_TARGETS 4
+
+layout (location = 7) in vec3 PositionDeltas[MAX_MORPH_TARGETS];
+layout (location = 11) in vec3 NormalDeltas[MAX_MORPH_TARGETS];
+layout (location = 15) in float Layer;
 
 out VS_OUTPUT {
     vec3 Color;
     vec2 TextureCoords;
-    float Brightness;
+    flat float Layer;
+    vec3 Brightness;
 } OUT;
 
 uniform mat4 camera;
 uniform mat4 transform;
 uniform mat4 transform_normal;
 
+#define MAX_JOINTS 64
+
+uniform bool skinned;
+uniform mat4 joints[MAX_JOINTS];
+uniform vec4 morph_weights;
+
+vec3 morph(vec3 base, vec3 deltas[MAX_MORPH_TARGETS]) {
+    for (int i = 0; i < MAX_MORPH_TARGETS; ++i) {
+        base += morph_weights[i] * deltas[i];
+    }
+    return base;
+}
+
+mat4 skin_matrix() {
+    if (!skinned) {
+        return mat4(1.0);
+    }
+    return Weights.x * joints[Joints.x]
+        + Weights.y * joints[Joints.y]
+        + Weights.z * joints[Joints.z]
+        + Weights.w * joints[Joints.w];
+}
+
 struct LightData {
     int mode; // 0 - off, 1 - point, 2 - projector
     vec3 location;
@@ -28,35 +60,44 @@ struct LightData {
 
 uniform struct LightData[LIGHT_COUNT] Light;
 
-float calculate_brightness() {
-    vec3 normal = normalize(vec3(transform_normal * vec4(Normal, 1.0) - transform_normal * vec4(0.0)));
-    vec3 positioin = vec3(transform * vec4(Position, 1.0));
+struct MaterialData {
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+    float shininess;
+};
 
-    float brightness = 0.0;
+uniform MaterialData Material;
+
+vec3 calculate_brightness(vec3 model_position, vec3 model_normal) {
+    vec3 normal = normalize(vec3(transform_normal * vec4(model_normal, 1.0) - transform_normal * vec4(0.0)));
+    vec3 positioin = vec3(transform * vec4(model_position, 1.0));
+
+    vec3 brightness = vec3(0.0);
     int active_lights = 0;
     for (int i = 0; i < LIGHT_COUNT; ++i)
     {
         vec3 light_vec = normalize(Light[i].location - positioin);
-        float new_brightness = 0.0;
+        vec3 new_brightness = vec3(0.0);
         
         switch (Light[i].mode)
         {
             case 1:
                 active_lights += 1;
-                new_brightness = Light[i].ambient
-                    + Light[i].diffuse * max(dot(normal, light_vec), 0.0) 
-                    + Light[i].specular * max(pow(dot(normal, ((normal * (2.0 * dot(normal, light_vec))) - light_vec)), 80.0), 0.0);
+                new_brightness = Light[i].ambient * Material.ambient
+                    + Light[i].diffuse * Material.diffuse * max(dot(normal, light_vec), 0.0) 
+                    + Light[i].specular * Material.specular * pow(max(dot(normal, ((normal * (2.0 * dot(normal, light_vec))) - light_vec)), 0.0), Material.shininess);
                 break;
             case 2:
                 active_lights += 1;
-                float ambient = Light[i].ambient;
+                vec3 ambient = Light[i].ambient * Material.ambient;
 
                 float diff = max(dot(normal, light_vec),0.0);
-                float diffuse = Light[i].diffuse * diff;
+                vec3 diffuse = Light[i].diffuse * Material.diffuse * diff;
 
                 vec3 halfwayDir = normalize(light_vec);
-                float spec = pow(max(dot(halfwayDir,normal), 0.0),32);
-                float specular = Light[i].specular * spec;
+                float spec = pow(max(dot(halfwayDir,normal), 0.0), Material.shininess);
+                vec3 specular = Light[i].specular * Material.specular * spec;
 
                 float theta = dot(light_vec, normalize(-Light[i].direction));
                 float epsilon = cos(0.3) - cos(0.6);
@@ -75,8 +116,13 @@ float calculate_brightness() {
 
 void main()
 {
-    gl_Position = camera * (transform * vec4(Position, 1.0));
+    mat4 skin = skin_matrix();
+    vec3 position = vec3(skin * vec4(morph(Position, PositionDeltas), 1.0));
+    vec3 normal = mat3(skin) * morph(Normal, NormalDeltas);
+
+    gl_Position = camera * (transform * vec4(position, 1.0));
     OUT.Color = Color;
     OUT.TextureCoords = TextureCoords;
-    OUT.Brightness = calculate_brightness();
+    OUT.Layer = Layer;
+    OUT.Brightness = calculate_brightness(position, normal);
 }
\ No newline at end of file
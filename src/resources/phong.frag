@@ -4,6 +4,7 @@ in VS_OUTPUT {
     vec3 Position;
     vec3 Color;
     vec2 TextureCoords;
+    flat float Layer;
     vec3 Normal;
 } IN;
 
@@ -11,6 +12,8 @@ out vec4 Color;
 
 uniform sampler2D Texture;
 uniform sampler2D Texture_2;
+uniform sampler2DArray Texture_Array;
+uniform bool layered;
 
 uniform float color_coeff;
 uniform float texture_coeff;
@@ -28,34 +31,43 @@ struct LightData {
 
 uniform struct LightData[LIGHT_COUNT] Light;
 
-float calculate_brightness() {
+struct MaterialData {
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+    float shininess;
+};
+
+uniform MaterialData Material;
+
+vec3 calculate_brightness() {
     vec3 normal = normalize(IN.Normal);
 
-    float brightness = 0.0;
+    vec3 brightness = vec3(0.0);
     int active_lights = 0;
     for (int i = 0; i < LIGHT_COUNT; ++i)
     {
         vec3 light_vec = normalize(Light[i].location - IN.Position);
-        float new_brightness = 0.0;
+        vec3 new_brightness = vec3(0.0);
         
         switch (Light[i].mode)
         {
             case 1:
                 active_lights += 1;
-                new_brightness = Light[i].ambient
-                    + Light[i].diffuse * max(dot(normal, light_vec), 0.0) 
-                    + Light[i].specular * max(pow(dot(normal, ((normal * (2.0 * dot(normal, light_vec))) - light_vec)), 80.0), 0.0);
+                new_brightness = Light[i].ambient * Material.ambient
+                    + Light[i].diffuse * Material.diffuse * max(dot(normal, light_vec), 0.0) 
+                    + Light[i].specular * Material.specular * pow(max(dot(normal, ((normal * (2.0 * dot(normal, light_vec))) - light_vec)), 0.0), Material.shininess);
                 break;
             case 2:
                 active_lights += 1;
-                float ambient = Light[i].ambient;
+                vec3 ambient = Light[i].ambient * Material.ambient;
 
                 float diff = max(dot(normal, light_vec),0.0);
-                float diffuse = Light[i].diffuse * diff;
+                vec3 diffuse = Light[i].diffuse * Material.diffuse * diff;
 
                 vec3 halfwayDir = normalize(light_vec/* + viewDir */);
-                float spec = pow(max(dot(halfwayDir,normal), 0.0),32);
-                float specular = Light[i].specular * spec;
+                float spec = pow(max(dot(halfwayDir,normal), 0.0), Material.shininess);
+                vec3 specular = Light[i].specular * Material.specular * spec;
 
                 float theta = dot(light_vec, normalize(-Light[i].direction));
                 float epsilon = cos(0.3) - cos(0.6);
@@ -72,7 +84,14 @@ float calculate_brightness() {
     return brightness / float(active_lights);
 }
 
+vec4 base_texture() {
+    if (layered) {
+        return texture(Texture_Array, vec3(IN.TextureCoords, IN.Layer));
+    }
+    return texture(Texture, IN.TextureCoords);
+}
+
 void main() {
-    float brightness = calculate_brightness();
-    Color = mix(vec4(IN.Color, 1.0f), mix(texture(Texture, IN.TextureCoords), texture(Texture_2, IN.TextureCoords), texture_coeff) * brightness, color_coeff);
+    vec3 brightness = calculate_brightness();
+    Color = mix(vec4(IN.Color, 1.0f), mix(base_texture(), texture(Texture_2, IN.TextureCoords), texture_coeff) * vec4(brightness, 1.0), color_coeff);
 }
\ No newline at end of file
@@ -4,11 +4,20 @@ layout (location = 0) in vec3 Position;
 layout (location = 1) in vec3 Color;
 layout (location = 2) in vec2 TextureCoords;
 layout (location = 3) in vec3 Normal;
+layout (location = 5) in uvec4 Joints;
+layout (location = 6) in vec4 Weights;
+
+#define MAX_MORPH_TARGETS 4
+
+layout (location = 7) in vec3 PositionDeltas[MAX_MORPH_TARGETS];
+layout (location = 11) in vec3 NormalDeltas[MAX_MORPH_TARGETS];
+layout (location = 15) in float Layer;
 
 out VS_OUTPUT {
     vec3 Position;
     vec3 Color;
     vec2 TextureCoords;
+    flat float Layer;
     vec3 Normal;
 } OUT;
 
@@ -16,11 +25,39 @@ uniform mat4 camera;
 uniform mat4 transform;
 uniform mat4 transform_normal;
 
+#define MAX_JOINTS 64
+
+uniform bool skinned;
+uniform mat4 joints[MAX_JOINTS];
+uniform vec4 morph_weights;
+
+vec3 morph(vec3 base, vec3 deltas[MAX_MORPH_TARGETS]) {
+    for (int i = 0; i < MAX_MORPH_TARGETS; ++i) {
+        base += morph_weights[i] * deltas[i];
+    }
+    return base;
+}
+
+mat4 skin_matrix() {
+    if (!skinned) {
+        return mat4(1.0);
+    }
+    return Weights.x * joints[Joints.x]
+        + Weights.y * joints[Joints.y]
+        + Weights.z * joints[Joints.z]
+        + Weights.w * joints[Joints.w];
+}
+
 void main()
 {
-    gl_Position = camera * (transform * vec4(Position, 1.0));
-    OUT.Position = vec3(transform * vec4(Position, 1.0));
+    mat4 skin = skin_matrix();
+    vec3 position = vec3(skin * vec4(morph(Position, PositionDeltas), 1.0));
+    vec3 normal = mat3(skin) * morph(Normal, NormalDeltas);
+
+    gl_Position = camera * (transform * vec4(position, 1.0));
+    OUT.Position = vec3(transform * vec4(position, 1.0));
     OUT.Color = Color;
     OUT.TextureCoords = TextureCoords;
-    OUT.Normal = vec3(transform_normal * vec4(Normal, 1.0) - transform_normal * vec4(0.0));
+    OUT.Layer = Layer;
+    OUT.Normal = vec3(transform_normal * vec4(normal, 1.0) - transform_normal * vec4(0.0));
 }
\ No newline at end of file
@@ -0,0 +1,11 @@
+#version 330 core
+
+in vec3 Direction;
+
+out vec4 Color;
+
+uniform samplerCube Sky;
+
+void main() {
+    Color = texture(Sky, Direction);
+}
\ No newline at end of file
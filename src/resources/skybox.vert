@@ -0,0 +1,14 @@
+#version 330 core
+
+layout (location = 0) in vec3 Position;
+
+out vec3 Direction;
+
+uniform mat4 rotation;
+
+void main()
+{
+    Direction = Position;
+    // Depth of 1, behind everything drawn before.
+    gl_Position = (rotation * vec4(Position, 1.0)).xyww;
+}
\ No newline at end of file
@@ -1,10 +1,14 @@
-use super::{
-    drawing::{Camera, Model, Program, Texture},
+use opengl_test::{
+    drawing::{
+        set_layer, Animator, Camera, Error, Interpolation, LodSelection, Lods, Material, Model,
+        MorphAnimation, MorphAnimator, ObjOptions, Program, Scene, Texture, TextureOptions,
+        UniformResult,
+    },
     entities::Entity,
 };
-use cgmath::{Matrix4, Point3, Rad, Vector3};
+use cgmath::{Matrix4, Point3, Rad, Vector3, Vector4, Zero};
 use gl::Gl;
-use std::rc::Rc;
+use std::{path::Path, rc::Rc};
 
 pub struct World {
     gl: Gl,
@@ -18,46 +22,97 @@ pub struct World {
 
 impl World {
     pub fn new(gl: Gl) -> Self {
+        // Every block type is a layer of one texture, so the terrain is drawn without rebinding.
+        let blocks = Texture::array_from_files(
+            gl.clone(),
+            &[
+                Path::new("src/resources/grass.png"),
+                Path::new("src/resources/stone.png"),
+            ],
+            TextureOptions::pixel_art(),
+        )
+        .unwrap();
+        const GRASS: u32 = 0;
+        const STONE: u32 = 1;
+
+        // The skull and cat keep their geometry to be simplified into levels of detail.
+        let detailed = ObjOptions {
+            keep_mesh_data: true,
+            ..ObjOptions::default()
+        };
         let models = vec![
             Rc::new(Model::cube(gl.clone())),
             Rc::new(
-                Model::from_obj(gl.clone(), std::path::Path::new("src/resources/skull.obj"))
-                    .unwrap(),
+                Model::from_obj_with_options(
+                    gl.clone(),
+                    std::path::Path::new("src/resources/skull.obj"),
+                    detailed,
+                )
+                .unwrap(),
             ),
+            // The flame end leans over in its only blend shape.
             Rc::new(
-                Model::from_obj(gl.clone(), std::path::Path::new("src/resources/torch.obj"))
-                    .unwrap(),
+                Model::from_obj_with_morph_targets(
+                    gl.clone(),
+                    std::path::Path::new("src/resources/torch.obj"),
+                    &[std::path::Path::new("src/resources/torch_sway.obj")],
+                )
+                .unwrap(),
             ),
             Rc::new(
-                Model::from_obj(gl.clone(), std::path::Path::new("src/resources/cat.obj"))
-                    .unwrap(),
+                Model::from_obj_with_options(
+                    gl.clone(),
+                    std::path::Path::new("src/resources/cat.obj"),
+                    detailed,
+                )
+                .unwrap(),
             ),
         ];
+        // Each level has half the triangles of the one before, taking over as the model
+        // gets smaller on screen.
+        let lods = |model: &Rc<Model>| {
+            Rc::new(
+                Lods::simplified(
+                    gl.clone(),
+                    model.clone(),
+                    4,
+                    LodSelection::ScreenSize(vec![0.4, 0.2, 0.1]),
+                )
+                .unwrap(),
+            )
+        };
+        let skull_lods = lods(&models[1]);
+        let cat_lods = lods(&models[3]);
         let textures = vec![
-            Rc::new(Texture::new(
-                gl.clone(),
-                std::path::Path::new("src/resources/grass.png"),
-            )),
-            Rc::new(Texture::new(
-                gl.clone(),
-                std::path::Path::new("src/resources/stone.png"),
-            )),
-            Rc::new(Texture::new(
-                gl.clone(),
-                std::path::Path::new("src/resources/skull.jpg"),
-            )),
-            Rc::new(Texture::new(
-                gl.clone(),
-                std::path::Path::new("src/resources/torch.png"),
-            )),
-            Rc::new(Texture::new(
-                gl.clone(),
-                std::path::Path::new("src/resources/aaa.png"),
-            )),
-            Rc::new(Texture::new(
-                gl.clone(),
-                std::path::Path::new("src/resources/cat.jpg"),
-            )),
+            Rc::new(blocks),
+            Rc::new(
+                Texture::with_options(
+                    gl.clone(),
+                    std::path::Path::new("src/resources/skull.jpg"),
+                    TextureOptions::smooth(),
+                )
+                .unwrap(),
+            ),
+            Rc::new(
+                Texture::with_options(
+                    gl.clone(),
+                    std::path::Path::new("src/resources/torch.png"),
+                    TextureOptions::smooth(),
+                )
+                .unwrap(),
+            ),
+            Rc::new(
+                Texture::new(gl.clone(), std::path::Path::new("src/resources/aaa.png"))
+                    .unwrap(),
+            ),
+            Rc::new(
+                Texture::with_options(
+                    gl.clone(),
+                    std::path::Path::new("src/resources/cat.jpg"),
+                    TextureOptions::smooth(),
+                )
+                .unwrap(),
+            ),
         ];
         // 0 - skull sun
         // 1 - camera light
@@ -69,55 +124,78 @@ impl World {
         ];
         let mut entities = vec![];
         // Small skull
-        entities.push(Entity::new(
-            models[1].clone(),
-            textures[2].clone(),
-            Matrix4::from_translation((0.0, 1.5, 0.0).into())
-                * Matrix4::from_scale(0.13)
-                * Matrix4::from_angle_x(Rad(std::f32::consts::PI / -2.0)),
-        ));
+        entities.push(
+            Entity::new(
+                models[1].clone(),
+                textures[1].clone(),
+                Matrix4::from_translation((0.0, 1.5, 0.0).into())
+                    * Matrix4::from_scale(0.13)
+                    * Matrix4::from_angle_x(Rad(std::f32::consts::PI / -2.0)),
+            )
+            .with_lods(skull_lods.clone()),
+        );
         // Torch
-        entities.push(Entity::new(
-            models[2].clone(),
-            textures[3].clone(),
-            Matrix4::from_translation((10.0, 1.0, 10.0).into()),
-        ));
+        let sway = MorphAnimation::new(
+            vec![0.0, 1.5, 3.0],
+            vec![Vector4::zero(), Vector4::new(1.0, 0.0, 0.0, 0.0), Vector4::zero()],
+            Interpolation::Linear,
+        );
+        entities.push(
+            Entity::new(
+                models[2].clone(),
+                textures[2].clone(),
+                Matrix4::from_translation((10.0, 1.0, 10.0).into()),
+            )
+            .with_morph(MorphAnimator::new(Rc::new(sway))),
+        );
         // Giant skull
-        entities.push(Entity::new(
-            models[1].clone(),
-            textures[2].clone(),
-            Matrix4::from_translation((0.0, 1.5, 0.0).into())
-                * Matrix4::from_scale(0.5)
-                * Matrix4::from_angle_x(Rad(std::f32::consts::PI / -2.0)),
-        ));
+        entities.push(
+            Entity::new(
+                models[1].clone(),
+                textures[1].clone(),
+                Matrix4::from_translation((0.0, 1.5, 0.0).into())
+                    * Matrix4::from_scale(0.5)
+                    * Matrix4::from_angle_x(Rad(std::f32::consts::PI / -2.0)),
+            )
+            .with_lods(skull_lods),
+        );
         // Blocks
         for i in -20..=20 {
             for j in -20..=20 {
                 let mut top = (((i as f32 / 4.0).sin() + (j as f32 / 5.0).cos()) / 2.0).round();
-                entities.push(Entity::new(
-                    models[0].clone(),
-                    textures[0].clone(),
-                    Matrix4::from_translation((i as f32, top, j as f32).into()),
-                ));
-                while top > -10.0 {
-                    top -= 1.0;
-                    entities.push(Entity::new(
+                entities.push(
+                    Entity::new(
                         models[0].clone(),
-                        textures[1].clone(),
+                        textures[0].clone(),
                         Matrix4::from_translation((i as f32, top, j as f32).into()),
-                    ));
+                    )
+                    .with_layer(GRASS),
+                );
+                while top > -10.0 {
+                    top -= 1.0;
+                    entities.push(
+                        Entity::new(
+                            models[0].clone(),
+                            textures[0].clone(),
+                            Matrix4::from_translation((i as f32, top, j as f32).into()),
+                        )
+                        .with_layer(STONE),
+                    );
                 }
             }
         }
         // Cat
-        entities.push(Entity::new(
-            models[3].clone(),
-            textures[5].clone(),
-            Matrix4::from_translation((10.0, -95.0, 10.0).into())
-                * Matrix4::from_scale(3.0)
-                * Matrix4::from_angle_x(Rad(std::f32::consts::PI / -2.0))
-                * Matrix4::from_angle_z(Rad(std::f32::consts::PI / 4.0)),
-        ));
+        entities.push(
+            Entity::new(
+                models[3].clone(),
+                textures[4].clone(),
+                Matrix4::from_translation((10.0, -95.0, 10.0).into())
+                    * Matrix4::from_scale(3.0)
+                    * Matrix4::from_angle_x(Rad(std::f32::consts::PI / -2.0))
+                    * Matrix4::from_angle_z(Rad(std::f32::consts::PI / 4.0)),
+            )
+            .with_lods(cat_lods),
+        );
         println!();
         Self {
             gl: gl.clone(),
@@ -126,19 +204,22 @@ impl World {
             lights,
             time: 0.0,
             color_coeff: 0.0,
-            texture_coeff: 1.0,
+            texture_coeff: 0.0,
         }
     }
 
-    pub fn tick(&mut self, camera: &Camera, program: &Program, secs: f32) {
+    /// Uniforms the current shaders strip are skipped, only the camera and transforms
+    /// have to be there.
+    pub fn tick(&mut self, camera: &Camera, program: &Program, secs: f32) -> Result<(), Error> {
         self.time += secs;
         unsafe {
             self.gl.Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
         program.set_used();
         let mut current_texture = self.textures[0].id;
-        self.textures[0].bind();
-        self.textures[4].bind_n(1);
+        let mut layered = self.textures[0].is_array();
+        self.textures[0].bind_n(if layered { 2 } else { 0 });
+        self.textures[3].bind_n(1);
 
 
         self.entities[0].matrix = Matrix4::from_translation(self.skull())
@@ -149,26 +230,91 @@ impl World {
         self.lights[0].set_location(Point3::from((0.0, 0.0, 0.0)) + skull);
 
         for (i, light) in self.lights.iter().enumerate() {
-            light.uniform(format!("Light[{}]", i).as_str(), &program);
+            light.uniform(format!("Light[{}]", i).as_str(), &program)?;
         }
-        program.set_matrix4("camera", &camera.matrix()).unwrap();
-        program.set_float("color_coeff", self.color_coeff).unwrap();
-        program.set_float("texture_coeff", self.texture_coeff).unwrap();
-        program.set_int("Texture", 0).unwrap();
-        program.set_int("Texture_2", 1).unwrap();
-
-        for entity in self.entities.iter() {
-            if entity.texture.id != current_texture {
-                entity.texture.bind();
-                self.textures[4].bind_n(1);
-                current_texture = entity.texture.id;
+        program.set_matrix4("camera", &camera.matrix())?;
+        program.set_float("color_coeff", self.color_coeff).or_stripped()?;
+        program.set_float("texture_coeff", self.texture_coeff).or_stripped()?;
+        program.set_int("Texture", 0).or_stripped()?;
+        program.set_int("Texture_2", 1).or_stripped()?;
+        program.set_int("Texture_Array", 2).or_stripped()?;
+        program.set_int("layered", layered as i32).or_stripped()?;
+        program.set_int("skinned", 0).or_stripped()?;
+        program.set_vec4("morph_weights", Vector4::zero()).or_stripped()?;
+
+        let default_material = Material::default();
+        let mut current_material: *const Material = std::ptr::null();
+        let mut skinned = false;
+        let mut morphed = false;
+        let mut current_layer = 0;
+        set_layer(&self.gl, current_layer);
+
+        for entity in self.entities.iter_mut() {
+            if let Some(animator) = &mut entity.animator {
+                animator.advance(secs);
+                program
+                    .set_matrix4_array("joints", animator.joint_matrices())
+                    .or_stripped()?;
+            }
+            if entity.animator.is_some() != skinned {
+                skinned = !skinned;
+                program.set_int("skinned", skinned as i32).or_stripped()?;
+            }
+            if let Some(morph) = &mut entity.morph {
+                morph.advance(secs);
+                program.set_vec4("morph_weights", morph.weights()).or_stripped()?;
+                morphed = true;
+            } else if morphed {
+                program.set_vec4("morph_weights", Vector4::zero()).or_stripped()?;
+                morphed = false;
+            }
+            if entity.layer != current_layer {
+                current_layer = entity.layer;
+                set_layer(&self.gl, current_layer);
             }
-            program.set_matrix4("transform", &entity.matrix()).unwrap();
+            program.set_matrix4("transform", &entity.matrix())?;
             program
                 .set_matrix4("transform_normal", &entity.normal_matrix())
-                .unwrap();
-            entity.model.draw(self.gl.clone());
+                .or_stripped()?;
+            for (mesh, texture) in entity.visible_meshes(camera) {
+                let material = mesh.material().map_or(&default_material, |m| m.as_ref());
+                if !std::ptr::eq(material, current_material) {
+                    material.uniform("Material", program)?;
+                    current_material = material;
+                }
+                if let Some(texture) = texture {
+                    if texture.id != current_texture {
+                        // Array textures have their own unit, the plain texture there is kept.
+                        texture.bind_n(if texture.is_array() { 2 } else { 0 });
+                        current_texture = texture.id;
+                    }
+                    if texture.is_array() != layered {
+                        layered = !layered;
+                        program.set_int("layered", layered as i32).or_stripped()?;
+                    }
+                }
+                mesh.draw(self.gl.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds every node of a glTF scene as an entity drawn with the scene's own materials.
+    /// Skinned nodes loop the first animation of their skin.
+    pub fn add_gltf(&mut self, file_name: &Path, matrix: Matrix4<f32>) -> Result<(), Error> {
+        let scene = Scene::from_gltf(self.gl.clone(), file_name)?;
+        for node in scene.nodes {
+            let mut entity = Entity::with_materials(node.model, matrix * node.matrix);
+            if let Some(skin) = node.skin {
+                let mut animator = Animator::new(skin);
+                if !animator.skin().animations.is_empty() {
+                    animator.play_index(0);
+                }
+                entity = entity.with_animator(animator);
+            }
+            self.entities.push(entity);
         }
+        Ok(())
     }
 
     pub fn turn_sun(&mut self) {
@@ -211,11 +357,11 @@ impl Light {
         };
     }
 
-    fn uniform(&self, name: &str, program: &Program) {
+    fn uniform(&self, name: &str, program: &Program) -> Result<(), Error> {
         match self {
             Self::Point(point) => point.uniform(name, program),
             Self::Projector(proj) => proj.uniform(name, program),
-        };
+        }
     }
 
     fn set_location(&mut self, location: Point3<f32>) {
@@ -245,25 +391,25 @@ impl CommonLightData {
         }
     }
 
-    fn uniform(&self, name: &str, program: &Program, mode: i32) {
+    fn uniform(&self, name: &str, program: &Program, mode: i32) -> Result<(), Error> {
         program
             .set_point3(format!("{}.location", name).as_str(), self.location)
-            .unwrap();
+            .or_stripped()?;
         program
             .set_int(
                 format!("{}.mode", name).as_str(),
                 if self.turned_on { mode } else { 0 },
             )
-            .unwrap();
+            .or_stripped()?;
         program
             .set_float(format!("{}.ambient", name).as_str(), self.ambient)
-            .unwrap();
+            .or_stripped()?;
         program
             .set_float(format!("{}.diffuse", name).as_str(), self.diffuse)
-            .unwrap();
+            .or_stripped()?;
         program
             .set_float(format!("{}.specular", name).as_str(), self.specular)
-            .unwrap();
+            .or_stripped()
     }
 }
 
@@ -286,8 +432,8 @@ impl PointLight {
         self.data.turned_on = state;
     }
 
-    fn uniform(&self, name: &str, program: &Program) {
-        self.data.uniform(name, program, 1);
+    fn uniform(&self, name: &str, program: &Program) -> Result<(), Error> {
+        self.data.uniform(name, program, 1)
     }
 }
 
@@ -318,10 +464,10 @@ impl ProjectorLight {
         self.data.turned_on = state;
     }
 
-    fn uniform(&self, name: &str, program: &Program) {
+    fn uniform(&self, name: &str, program: &Program) -> Result<(), Error> {
         program
             .set_vec3(format!("{}.direction", name).as_str(), self.direction)
-            .unwrap();
-        self.data.uniform(name, program, 2);
+            .or_stripped()?;
+        self.data.uniform(name, program, 2)
     }
 }